@@ -0,0 +1,311 @@
+//! Claim check：正文超过阈值的消息写入 [`BlobStore`]，队列中只保存引用
+//! 引用格式为 `mns-claim-check:v1:` 加上 blob key，接收时按引用取回正文
+//! 开启 [`ClaimCheckOptions::delete_on_ack`] 后引用格式为 `mns-claim-check:v2:` + HMAC + `:` + blob key，
+//! 接收时先校验 HMAC，伪造的引用不会被读取或删除
+//!
+//! 内置本地文件和内存两种存储，OSS 等其他存储可以自行实现 [`BlobStore`]
+//!
+//! # Example
+//! ```rust,no_run
+//! use mns::claim_check::{ClaimCheckOptions, FsBlobStore};
+//! use mns::{Client, Queue};
+//! #[tokio::main]
+//! async fn main() {
+//!     let client = Client::new("https://xxx.mns.cn-hangzhou.aliyuncs.com", "your id", "your key");
+//!     let queue = Queue::new("your queue name", &client).with_claim_check(ClaimCheckOptions::new(
+//!         FsBlobStore::new("/mnt/shared/mns-blobs"),
+//!         60 * 1024,
+//!     ));
+//!     queue.send_bytes(&vec![b'a'; 1024 * 1024]).await.unwrap();
+//! }
+//! ```
+use crate::error::Error::{BlobStoreFailed, InvalidArgument, MessageTampered};
+use crate::error::Result;
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+const PREFIX: &[u8] = b"mns-claim-check:v1:";
+const SIGNED_PREFIX: &[u8] = b"mns-claim-check:v2:";
+
+/// 存放大消息正文的存储
+#[async_trait]
+pub trait BlobStore: Debug + Send + Sync {
+    /// 写入正文，返回之后用于读取的 key
+    async fn put(&self, data: &[u8]) -> Result<String>;
+    async fn get(&self, key: &str) -> Result<Vec<u8>>;
+    async fn delete(&self, key: &str) -> Result<()>;
+}
+
+/// Claim check 设置
+#[derive(Clone)]
+pub struct ClaimCheckOptions {
+    pub store: Arc<dyn BlobStore>,
+    /// 正文（压缩、加密之后）超过该字节数时写入 store
+    pub threshold: usize,
+    /// 引用的签名密钥，设置后 ack 时删除 blob
+    signing_key: Option<Arc<[u8]>>,
+}
+
+impl Debug for ClaimCheckOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClaimCheckOptions")
+            .field("store", &self.store)
+            .field("threshold", &self.threshold)
+            .field("delete_on_ack", &self.signing_key.is_some())
+            .finish()
+    }
+}
+
+impl ClaimCheckOptions {
+    pub fn new<S: BlobStore + 'static>(store: S, threshold: usize) -> Self {
+        Self {
+            store: Arc::new(store),
+            threshold,
+            signing_key: None,
+        }
+    }
+
+    /// ack 时同时删除 blob
+    /// 引用中带上用 signing_key 计算的 HMAC，接收时校验，避免按伪造的引用删除其他 blob，
+    /// 生产者和消费者需要使用相同的 signing_key
+    pub fn delete_on_ack(mut self, signing_key: &[u8]) -> Self {
+        self.signing_key = Some(signing_key.into());
+        self
+    }
+
+    pub(crate) fn deletes_on_ack(&self) -> bool {
+        self.signing_key.is_some()
+    }
+
+    fn mac(&self, key: &str) -> Option<Hmac<Sha1>> {
+        let mut mac = Hmac::<Sha1>::new_from_slice(self.signing_key.as_deref()?).ok()?;
+        mac.update(key.as_bytes());
+        Some(mac)
+    }
+
+    pub(crate) fn reference(&self, key: &str) -> Vec<u8> {
+        match self.mac(key) {
+            Some(mac) => {
+                let mut buf = [0u8; 40];
+                let sig = base16ct::lower::encode_str(&mac.finalize().into_bytes(), &mut buf)
+                    .unwrap()
+                    .as_bytes();
+                [SIGNED_PREFIX, sig, b":", key.as_bytes()].concat()
+            }
+            None => [PREFIX, key.as_bytes()].concat(),
+        }
+    }
+
+    /// 引用中的 blob key，设置了签名密钥时只接受签名正确的引用
+    pub(crate) fn verify<'a>(&self, r: &Reference<'a>) -> Result<&'a str> {
+        let Some(mac) = self.mac(r.key) else {
+            return Ok(r.key);
+        };
+        let mut buf = [0u8; 20];
+        let sig = r
+            .signature
+            .and_then(|s| base16ct::lower::decode(s, &mut buf).ok())
+            .ok_or(MessageTampered)?;
+        mac.verify_slice(sig).map_err(|_| MessageTampered)?;
+        Ok(r.key)
+    }
+}
+
+/// 消息正文中的 blob 引用
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Reference<'a> {
+    pub key: &'a str,
+    signature: Option<&'a str>,
+}
+
+/// 正文是引用时返回其中的 blob key 和签名
+pub(crate) fn parse_reference(data: &[u8]) -> Option<Reference<'_>> {
+    if let Some(r) = data.strip_prefix(PREFIX) {
+        let key = std::str::from_utf8(r).ok()?;
+        return Some(Reference {
+            key,
+            signature: None,
+        });
+    }
+    let r = std::str::from_utf8(data.strip_prefix(SIGNED_PREFIX)?).ok()?;
+    let (signature, key) = r.split_once(':')?;
+    Some(Reference {
+        key,
+        signature: Some(signature),
+    })
+}
+
+/// 进程内唯一的 key
+fn new_key() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    format!(
+        "{}-{}-{}",
+        time::OffsetDateTime::now_utc().unix_timestamp_nanos(),
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// key 来自消息，使用前检查，避免访问存储目录以外的文件
+fn check_key(key: &str) -> Result<()> {
+    let valid = !key.is_empty()
+        && !key.starts_with('.')
+        && key
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"-_.".contains(&b));
+    if valid {
+        Ok(())
+    } else {
+        Err(InvalidArgument(format!("invalid blob key {key}")))
+    }
+}
+
+/// 本地文件存储，多个进程共享时需要使用共享目录
+#[cfg(feature = "tokio")]
+#[derive(Debug, Clone)]
+pub struct FsBlobStore {
+    dir: std::path::PathBuf,
+}
+
+#[cfg(feature = "tokio")]
+impl FsBlobStore {
+    pub fn new<P: Into<std::path::PathBuf>>(dir: P) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[async_trait]
+impl BlobStore for FsBlobStore {
+    async fn put(&self, data: &[u8]) -> Result<String> {
+        let key = new_key();
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .map_err(|e| BlobStoreFailed(e.to_string()))?;
+        tokio::fs::write(self.dir.join(&key), data)
+            .await
+            .map_err(|e| BlobStoreFailed(e.to_string()))?;
+        Ok(key)
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>> {
+        check_key(key)?;
+        tokio::fs::read(self.dir.join(key))
+            .await
+            .map_err(|e| BlobStoreFailed(format!("{key}: {e}")))
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        check_key(key)?;
+        match tokio::fs::remove_file(self.dir.join(key)).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(BlobStoreFailed(format!("{key}: {e}")))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// 内存存储，只能在同一进程内使用，适合测试
+#[derive(Debug, Clone, Default)]
+pub struct MemoryBlobStore {
+    blobs: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+}
+
+impl MemoryBlobStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.blobs.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[async_trait]
+impl BlobStore for MemoryBlobStore {
+    async fn put(&self, data: &[u8]) -> Result<String> {
+        let key = new_key();
+        self.blobs
+            .lock()
+            .unwrap()
+            .insert(key.clone(), data.to_vec());
+        Ok(key)
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>> {
+        self.blobs
+            .lock()
+            .unwrap()
+            .get(key)
+            .cloned()
+            .ok_or_else(|| BlobStoreFailed(format!("{key} not found")))
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.blobs.lock().unwrap().remove(key);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_reference() {
+        let opt = ClaimCheckOptions::new(MemoryBlobStore::new(), 8);
+        let r = opt.reference("k1");
+        assert_eq!(b"mns-claim-check:v1:k1".to_vec(), r);
+        assert_eq!("k1", opt.verify(&parse_reference(&r).unwrap()).unwrap());
+        assert_eq!(None, parse_reference(b"hello"));
+        assert!(check_key(&new_key()).is_ok());
+        assert!(check_key("../etc/passwd").is_err());
+        assert!(check_key("a/b").is_err());
+    }
+
+    #[test]
+    fn test_signed_reference() {
+        let opt = ClaimCheckOptions::new(MemoryBlobStore::new(), 8).delete_on_ack(b"secret");
+        let r = opt.reference("k1");
+        assert!(r.starts_with(SIGNED_PREFIX));
+        assert_eq!("k1", opt.verify(&parse_reference(&r).unwrap()).unwrap());
+
+        // 未签名、签名错误、替换了 key 的引用都会被拒绝
+        let unsigned = parse_reference(b"mns-claim-check:v1:k1").unwrap();
+        assert!(matches!(opt.verify(&unsigned), Err(MessageTampered)));
+        let other = ClaimCheckOptions::new(MemoryBlobStore::new(), 8).delete_on_ack(b"other");
+        let forged = other.reference("k1");
+        assert!(matches!(
+            opt.verify(&parse_reference(&forged).unwrap()),
+            Err(MessageTampered)
+        ));
+        let mut swapped = r.clone();
+        *swapped.last_mut().unwrap() = b'2';
+        assert!(matches!(
+            opt.verify(&parse_reference(&swapped).unwrap()),
+            Err(MessageTampered)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_fs_store() {
+        let dir = std::env::temp_dir().join(format!("mns-blob-{}", new_key()));
+        let s = FsBlobStore::new(&dir);
+        let key = s.put(b"hello").await.unwrap();
+        assert_eq!(b"hello".to_vec(), s.get(&key).await.unwrap());
+        s.delete(&key).await.unwrap();
+        assert!(s.get(&key).await.is_err());
+        s.delete(&key).await.unwrap();
+        tokio::fs::remove_dir_all(dir).await.unwrap();
+    }
+}
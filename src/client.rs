@@ -1,28 +1,127 @@
+#[cfg(feature = "reqwest")]
+pub use crate::client_builder::TlsBackend;
+pub use crate::client_builder::{ClientBuilder, NetworkType};
+use crate::credentials::{CredentialsProvider, StaticCredentials};
+use crate::queue::ErrorResponse;
+use crate::retry::{Failure, RetryPolicy};
+use crate::transport::{Transport, TransportRequest, TransportResponse};
 use anyhow::Result;
 use base64::{engine::general_purpose::STANDARD, Engine as _};
 use hmac::{Hmac, Mac};
+use http::header::{HeaderName, HeaderValue};
+use http::{HeaderMap, Method, StatusCode};
 use md5::{Digest, Md5};
-use reqwest::{Method, StatusCode};
 use sha1::Sha1;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// STS 临时凭证的请求头
+const SECURITY_TOKEN: &str = "security-token";
+/// 参与签名的 STS 临时凭证请求头，放在 CanonicalizedMNSHeaders 中
+const MNS_SECURITY_TOKEN: &str = "x-mns-security-token";
 
 #[derive(Debug, Clone)]
 pub struct Client {
     endpoint: String,
-    id: String,
-    sec: String,
-    client: reqwest::Client,
+    credentials: Arc<dyn CredentialsProvider>,
+    transport: Arc<dyn Transport>,
+    retry: RetryPolicy,
+    /// 没有指定超时时间的请求使用的超时时间
+    timeout: Duration,
+    user_agent: Option<String>,
 }
 
 impl Client {
+    /// 使用 reqwest 发送请求
+    #[cfg(feature = "reqwest")]
     pub fn new(endpoint: &str, id: &str, sec: &str) -> Self {
+        Self::with_transport(
+            endpoint,
+            id,
+            sec,
+            crate::transport::ReqwestTransport::default(),
+        )
+    }
+
+    /// 使用指定的 [`Transport`] 发送请求
+    pub fn with_transport<T: Transport + 'static>(
+        endpoint: &str,
+        id: &str,
+        sec: &str,
+        transport: T,
+    ) -> Self {
+        Self::with_credentials(endpoint, StaticCredentials::new(id, sec), transport)
+    }
+
+    /// 每次请求前向 [`CredentialsProvider`] 获取凭证，用于 STS 等会过期的临时凭证
+    pub fn with_credentials<P, T>(endpoint: &str, credentials: P, transport: T) -> Self
+    where
+        P: CredentialsProvider + 'static,
+        T: Transport + 'static,
+    {
+        Self::from_parts(endpoint, Arc::new(credentials), Arc::new(transport))
+    }
+
+    pub(crate) fn from_parts(
+        endpoint: &str,
+        credentials: Arc<dyn CredentialsProvider>,
+        transport: Arc<dyn Transport>,
+    ) -> Self {
         Self {
             endpoint: endpoint.to_string(),
-            id: id.to_string(),
-            sec: sec.to_string(),
-            client: reqwest::Client::new(),
+            credentials,
+            transport,
+            retry: RetryPolicy::default(),
+            timeout: Duration::from_secs(5),
+            user_agent: None,
+        }
+    }
+
+    /// 使用 [`ClientBuilder`] 按账号和地域创建 Client
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::default()
+    }
+
+    /// 更换凭证，其余设置不变
+    /// 带 SecurityToken 的 STS 临时凭证会通过 `security-token` 请求头发送，
+    /// 同时以 `x-mns-security-token` 参与签名
+    pub fn with_credentials_provider<P: CredentialsProvider + 'static>(
+        mut self,
+        credentials: P,
+    ) -> Self {
+        self.credentials = Arc::new(credentials);
+        self
+    }
+
+    /// 返回使用指定重试策略的 Client，其余设置不变
+    /// 可以只为某次调用更换策略，例如 `Queue::new(name, &client.with_retry_policy(RetryPolicy::none()))`
+    pub fn with_retry_policy(&self, retry: RetryPolicy) -> Self {
+        Self {
+            retry,
+            ..self.clone()
         }
     }
+
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry
+    }
+
+    /// 设置默认的请求超时时间，默认为 5 秒，长轮询等指定了超时时间的请求不受影响
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = Some(user_agent.to_string());
+        self
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
     pub async fn request(
         &self,
         resource: &str,
@@ -31,6 +130,72 @@ impl Client {
         body: &str,
         timeout_sec: Option<i32>,
     ) -> Result<(StatusCode, Vec<u8>)> {
+        self.request_with_headers(resource, method, content_type, body, timeout_sec, &[])
+            .await
+    }
+
+    /// 与 [`Client::request`] 相同，额外携带请求头
+    /// 其中 `x-mns-*` 开头的请求头会参与签名，例如分页接口的 `x-mns-marker`
+    pub async fn request_with_headers(
+        &self,
+        resource: &str,
+        method: &str,
+        content_type: &str,
+        body: &str,
+        timeout_sec: Option<i32>,
+        headers: &[(&str, &str)],
+    ) -> Result<(StatusCode, Vec<u8>)> {
+        let method = Method::from_str(method)?;
+        let mut attempt = 1;
+        loop {
+            // 每次重试重新签名，保证 Date 是最新的
+            let res = self
+                .send_once(resource, &method, content_type, body, timeout_sec, headers)
+                .await;
+            let retry = match &res {
+                Ok(r) if r.status.is_success() => false,
+                // 批量接口部分失败时返回逐条的结果，其中成功的部分已经处理，交给调用方
+                Ok(r) if is_batch_result(&r.body) => false,
+                Ok(r) => {
+                    let code = serde_xml_rs::from_reader::<_, ErrorResponse>(r.body.as_slice())
+                        .ok()
+                        .map(|e| e.code);
+                    self.retry.should_retry(
+                        attempt,
+                        &method,
+                        &Failure::Response {
+                            status: r.status,
+                            code: code.as_deref(),
+                        },
+                    )
+                }
+                Err(e) => self
+                    .retry
+                    .should_retry(attempt, &method, &Failure::Transport(e)),
+            };
+            // 没有 tokio 时没有异步定时器，不重试
+            if !retry || cfg!(not(feature = "tokio")) {
+                let res = res?;
+                return Ok((res.status, res.body));
+            }
+            let delay = self.retry.delay(attempt);
+            tracing::debug!("retry {method} {resource} after {delay:?}, attempt {attempt}");
+            #[cfg(feature = "tokio")]
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    async fn send_once(
+        &self,
+        resource: &str,
+        method: &Method,
+        content_type: &str,
+        body: &str,
+        timeout_sec: Option<i32>,
+        headers: &[(&str, &str)],
+    ) -> Result<TransportResponse> {
+        let credentials = self.credentials.credentials().await?;
         let date = gmt_now()?;
         let m = {
             let mut hasher = Md5::new();
@@ -41,36 +206,75 @@ impl Client {
             STANDARD.encode(m)
         };
 
-        let s = req_sign(
-            &self.sec,
-            method.to_string(),
+        let mut mns_headers = vec![("x-mns-version".to_string(), "2015-06-06".to_string())];
+        mns_headers.extend(
+            headers
+                .iter()
+                .map(|(k, v)| (k.to_lowercase(), v.to_string())),
+        );
+        if let Some(token) = &credentials.security_token {
+            mns_headers.push((MNS_SECURITY_TOKEN.to_string(), token.clone()));
+        }
+
+        let s = req_sign_with_headers(
+            &credentials.access_key_secret,
+            method.as_str().to_string(),
             m.to_string(),
             date.clone(),
+            &mns_headers,
             resource.to_string(),
         )?;
 
-        let res = self
-            .client
-            .request(
-                Method::from_str(method)?,
-                format!("{}{}", self.endpoint, resource).as_str(),
-            )
-            .header("Date", date)
-            .header("Authorization", format!("MNS {}:{}", self.id, s))
-            .header("Content-Type", content_type)
-            .header("Content-Md5", m)
-            .header("x-mns-version", "2015-06-06")
-            .timeout(std::time::Duration::from_secs(
-                timeout_sec.unwrap_or(5) as u64
-            ))
-            .body(body.to_string())
-            .send()
-            .await?;
-
-        Ok((res.status(), res.bytes().await?.as_ref().to_vec()))
+        let mut req_headers = HeaderMap::new();
+        req_headers.insert("Date", HeaderValue::from_str(&date)?);
+        req_headers.insert(
+            "Authorization",
+            HeaderValue::from_str(&format!("MNS {}:{}", credentials.access_key_id, s))?,
+        );
+        req_headers.insert("Content-Type", HeaderValue::from_str(content_type)?);
+        req_headers.insert("Content-Md5", HeaderValue::from_str(&m)?);
+        if let Some(ua) = &self.user_agent {
+            req_headers.insert("User-Agent", HeaderValue::from_str(ua)?);
+        }
+        for (k, v) in &mns_headers {
+            req_headers.insert(HeaderName::from_str(k)?, HeaderValue::from_str(v)?);
+        }
+        if let Some(token) = &credentials.security_token {
+            req_headers.insert(SECURITY_TOKEN, HeaderValue::from_str(token)?);
+        }
+        self.transport
+            .send(TransportRequest {
+                method: method.clone(),
+                url: format!("{}{}", self.endpoint, resource),
+                headers: req_headers,
+                body: body.as_bytes().to_vec(),
+                timeout: timeout_sec
+                    .map(|t| Duration::from_secs(t as u64))
+                    .unwrap_or(self.timeout),
+            })
+            .await
     }
 }
 
+/// 响应是否为批量接口逐条的结果，即根节点为 Messages 或 Errors
+fn is_batch_result(body: &[u8]) -> bool {
+    let body = String::from_utf8_lossy(body);
+    let mut rest = body.trim_start();
+    // 跳过 XML 声明和注释
+    while rest.starts_with("<?") || rest.starts_with("<!--") {
+        let end = if rest.starts_with("<?") { "?>" } else { "-->" };
+        match rest.find(end) {
+            Some(i) => rest = rest[i + end.len()..].trim_start(),
+            None => return false,
+        }
+    }
+    ["<Messages", "<Errors"].iter().any(|root| {
+        rest.strip_prefix(root)
+            .is_some_and(|r| r.starts_with(['>', ' ', '/', '\n', '\r', '\t']))
+    })
+}
+
+#[cfg(test)]
 fn req_sign(
     sk: &str,
     method: String,
@@ -78,12 +282,57 @@ fn req_sign(
     date: String,
     resource: String,
 ) -> Result<String> {
-    let s = format!(
-        "{method}\n{lower_md5_base64}\napplication/xml\n{date}\nx-mns-version:2015-06-06\n{resource}"
+    req_sign_with_headers(
+        sk,
+        method,
+        lower_md5_base64,
+        date,
+        &[("x-mns-version".to_string(), "2015-06-06".to_string())],
+        resource,
+    )
+}
+
+fn req_sign_with_headers(
+    sk: &str,
+    method: String,
+    lower_md5_base64: String,
+    date: String,
+    headers: &[(String, String)],
+    resource: String,
+) -> Result<String> {
+    let s = string_to_sign(
+        &method,
+        &lower_md5_base64,
+        "application/xml",
+        &date,
+        headers,
+        &resource,
     );
     sign(sk, s.as_str())
 }
 
+/// 按 MNS 签名规则拼接待签名字符串
+/// CanonicalizedMNSHeaders 由所有 `x-mns-` 开头的请求头按名称排序后拼接而成
+pub(crate) fn string_to_sign(
+    method: &str,
+    content_md5: &str,
+    content_type: &str,
+    date: &str,
+    headers: &[(String, String)],
+    resource: &str,
+) -> String {
+    let mut mns_headers: Vec<_> = headers
+        .iter()
+        .filter(|(k, _)| k.starts_with("x-mns-"))
+        .collect();
+    mns_headers.sort_by(|a, b| a.0.cmp(&b.0));
+    let canonicalized_headers: String = mns_headers
+        .iter()
+        .map(|(k, v)| format!("{k}:{v}\n"))
+        .collect();
+    format!("{method}\n{content_md5}\n{content_type}\n{date}\n{canonicalized_headers}{resource}")
+}
+
 fn sign<S: Into<String>>(key: S, body: &str) -> Result<String> {
     let mut mac = Hmac::<Sha1>::new_from_slice(key.into().as_bytes())?;
     mac.update(body.as_bytes());
@@ -92,7 +341,7 @@ fn sign<S: Into<String>>(key: S, body: &str) -> Result<String> {
     Ok(s)
 }
 
-fn gmt_now() -> Result<String> {
+pub(crate) fn gmt_now() -> Result<String> {
     Ok(time::OffsetDateTime::now_utc()
         .format(&time::format_description::well_known::Rfc2822)?
         .split("+0")
@@ -143,6 +392,53 @@ Thu, 02 Feb 2023 02:09:48 GMT
         assert_eq!("zVO3Buq0YfEW1yLI0SXOaO6guq8=", r);
     }
 
+    #[test]
+    fn test_sign_with_headers() {
+        let version = ("x-mns-version".to_string(), "2015-06-06".to_string());
+        let r = req_sign_with_headers(
+            "t5I8e",
+            "POST".to_string(),
+            "YTM5OGY1YmYxODRkY2M0YmM1NjU5OGYzYTJkMDMyZGQ=".to_string(),
+            "Thu, 02 Feb 2023 12:27:22 GMT".to_string(),
+            std::slice::from_ref(&version),
+            "/queues/market-process-log/messages".to_string(),
+        )
+        .unwrap();
+        assert_eq!("6nhdhorU7xdV6x+P1Tmzyi6A6KY=", r);
+
+        // 请求头的顺序不影响签名，非 x-mns- 开头的请求头不参与签名
+        let marker = ("x-mns-marker".to_string(), "abc".to_string());
+        let prefix = ("x-mns-prefix".to_string(), "q".to_string());
+        let other = ("security".to_string(), "x".to_string());
+        let a = req_sign_with_headers(
+            "t5I8e",
+            "GET".to_string(),
+            "".to_string(),
+            "Thu, 02 Feb 2023 12:27:22 GMT".to_string(),
+            &[version.clone(), marker.clone(), prefix.clone()],
+            "/queues".to_string(),
+        )
+        .unwrap();
+        let b = req_sign_with_headers(
+            "t5I8e",
+            "GET".to_string(),
+            "".to_string(),
+            "Thu, 02 Feb 2023 12:27:22 GMT".to_string(),
+            &[prefix, other, marker, version],
+            "/queues".to_string(),
+        )
+        .unwrap();
+        assert_eq!(a, b);
+        assert_eq!(
+            a,
+            sign(
+                "t5I8e",
+                "GET\n\napplication/xml\nThu, 02 Feb 2023 12:27:22 GMT\nx-mns-marker:abc\nx-mns-prefix:q\nx-mns-version:2015-06-06\n/queues"
+            )
+            .unwrap()
+        );
+    }
+
     #[tokio::test]
     async fn test_sign_req() {
         let c = Client::new(
@@ -191,6 +487,140 @@ Thu, 02 Feb 2023 02:09:48 GMT
         );
     }
 
+    #[tokio::test]
+    async fn test_retry() {
+        use crate::retry::RetryPolicy;
+        use crate::transport::MemoryTransport;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let error = |code: &str| {
+            format!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+<Error xmlns="http://mns.aliyuncs.com/doc/v1/">
+  <Code>{code}</Code>
+  <Message>{code}</Message>
+  <RequestId>5F290C926D472878-2-14D9529A8FA-200000003</RequestId>
+  <HostId>http://123.mns.cn-hangzhou.aliyuncs.com</HostId>
+</Error>"#
+            )
+        };
+        let n = Arc::new(AtomicUsize::new(0));
+        let counter = n.clone();
+        // 奇数次请求返回错误，偶数次成功
+        let t = MemoryTransport::new(move |req| {
+            if counter.fetch_add(1, Ordering::SeqCst) % 2 == 1 {
+                TransportResponse::new(StatusCode::OK, "")
+            } else if req.url.ends_with("/qps") {
+                TransportResponse::new(StatusCode::SERVICE_UNAVAILABLE, error("QpsLimitExceeded"))
+            } else {
+                TransportResponse::new(StatusCode::INTERNAL_SERVER_ERROR, error("InternalError"))
+            }
+        });
+        let c = Client::with_transport("http://mns.test", "id", "sec", t.clone())
+            .with_retry_policy(RetryPolicy {
+                base_delay: std::time::Duration::from_millis(1),
+                ..RetryPolicy::default()
+            });
+
+        let (status, _) = c.request("/queues/q1", "GET", "", "", None).await.unwrap();
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!(2, t.requests().len());
+
+        // POST 不是幂等的，InternalError 时服务端可能已经处理了请求，不重试
+        let (status, _) = c
+            .request("/queues/q1/messages", "POST", "", "", None)
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, status);
+        assert_eq!(3, t.requests().len());
+
+        n.store(0, Ordering::SeqCst);
+        let (status, _) = c.request("/qps", "POST", "", "", None).await.unwrap();
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!(5, t.requests().len());
+
+        n.store(0, Ordering::SeqCst);
+        let c = c.with_retry_policy(RetryPolicy::none());
+        let (status, _) = c.request("/queues/q1", "GET", "", "", None).await.unwrap();
+        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, status);
+        assert_eq!(6, t.requests().len());
+    }
+
+    #[tokio::test]
+    async fn test_no_retry_batch_result() {
+        use crate::transport::MemoryTransport;
+
+        // 部分失败的批量删除，第一条成功、第二条失败
+        let body = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Errors xmlns="http://mns.aliyuncs.com/doc/v1/">
+  <Error>
+    <ErrorCode>ReceiptHandleError</ErrorCode>
+    <ErrorMessage>The receipt handle you provide is not valid.</ErrorMessage>
+    <ReceiptHandle>h2</ReceiptHandle>
+  </Error>
+</Errors>"#;
+        let t = MemoryTransport::new(move |_| {
+            TransportResponse::new(StatusCode::INTERNAL_SERVER_ERROR, body)
+        });
+        let c = Client::with_transport("http://mns.test", "id", "sec", t.clone());
+        let (status, _) = c
+            .request("/queues/q1/messages", "DELETE", "", "", None)
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, status);
+        assert_eq!(1, t.requests().len());
+
+        assert!(is_batch_result(b"<Messages><Message/></Messages>"));
+        assert!(is_batch_result(b"<?xml version=\"1.0\"?>\n<Errors/>"));
+        assert!(!is_batch_result(
+            b"<Error><Code>InternalError</Code></Error>"
+        ));
+        assert!(!is_batch_result(b"<MessagesX/>"));
+        assert!(!is_batch_result(b"bad gateway"));
+    }
+
+    #[tokio::test]
+    async fn test_security_token() {
+        use crate::transport::MemoryTransport;
+
+        let t = MemoryTransport::new(|_| TransportResponse::new(StatusCode::OK, ""));
+        let c = Client::with_transport("http://mns.test", "id", "sec", t.clone())
+            .with_credentials_provider(
+                StaticCredentials::new("STS.id", "sec").with_security_token("token"),
+            );
+        c.request("/queues/q1", "GET", "application/xml", "", None)
+            .await
+            .unwrap();
+        let req = &t.requests()[0];
+        assert_eq!("token", req.headers["security-token"]);
+        assert_eq!("token", req.headers["x-mns-security-token"]);
+        let date = req.headers["Date"].to_str().unwrap();
+        // 只有 x-mns-security-token 在 CanonicalizedMNSHeaders 中
+        let expected = format!(
+            "GET\n{}\napplication/xml\n{date}\nx-mns-security-token:token\nx-mns-version:2015-06-06\n/queues/q1",
+            req.headers["Content-Md5"].to_str().unwrap()
+        );
+        assert_eq!(
+            expected,
+            string_to_sign(
+                "GET",
+                req.headers["Content-Md5"].to_str().unwrap(),
+                "application/xml",
+                date,
+                &[
+                    ("x-mns-version".to_string(), "2015-06-06".to_string()),
+                    (SECURITY_TOKEN.to_string(), "token".to_string()),
+                    (MNS_SECURITY_TOKEN.to_string(), "token".to_string()),
+                ],
+                "/queues/q1",
+            )
+        );
+        assert_eq!(
+            format!("MNS STS.id:{}", sign("sec", &expected).unwrap()),
+            req.headers["Authorization"]
+        );
+    }
+
     #[test]
     fn test_gmt() {
         dbg!(gmt_now().unwrap());
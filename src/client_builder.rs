@@ -0,0 +1,334 @@
+//! 按账号和地域创建 [`Client`]，并配置底层的 HTTP 客户端
+//!
+//! # Example
+//! ```rust,no_run
+//! use mns::client::NetworkType;
+//! use mns::Client;
+//! use std::time::Duration;
+//!
+//! let client = Client::builder()
+//!     .account("1234567890")
+//!     .region("cn-hangzhou")
+//!     .network(NetworkType::Vpc)
+//!     .credentials("your id", "your key")
+//!     .timeout(Duration::from_secs(10))
+//!     .build()
+//!     .unwrap();
+//! ```
+use crate::credentials::{ChainCredentials, CredentialsProvider, StaticCredentials};
+use crate::error::Error::InvalidArgument;
+use crate::error::Result;
+use crate::retry::RetryPolicy;
+use crate::transport::Transport;
+use crate::Client;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 访问 MNS 的网络类型，决定 endpoint 的域名
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NetworkType {
+    /// 公网，`{account}.mns.{region}.aliyuncs.com`
+    #[default]
+    Public,
+    /// 同地域经典网络内网，`{account}.mns.{region}-internal.aliyuncs.com`
+    Internal,
+    /// 同地域 VPC 内网，`{account}.mns.{region}-internal-vpc.aliyuncs.com`
+    Vpc,
+}
+
+impl NetworkType {
+    fn suffix(&self) -> &'static str {
+        match self {
+            NetworkType::Public => "",
+            NetworkType::Internal => "-internal",
+            NetworkType::Vpc => "-internal-vpc",
+        }
+    }
+}
+
+/// reqwest 使用的 TLS 实现
+#[cfg(feature = "reqwest")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TlsBackend {
+    #[default]
+    Rustls,
+    /// 系统的 TLS 实现，需要开启 `native-tls` feature
+    #[cfg(feature = "native-tls")]
+    NativeTls,
+}
+
+/// [`Client`] 的构造器
+/// 没有指定凭证时使用 [`ChainCredentials::default_chain`]，
+/// 没有指定 [`Transport`] 时按照连接相关的设置创建 reqwest 客户端
+#[derive(Debug, Default)]
+pub struct ClientBuilder {
+    endpoint: Option<String>,
+    account: Option<String>,
+    region: Option<String>,
+    network: NetworkType,
+    credentials: Option<Arc<dyn CredentialsProvider>>,
+    transport: Option<Arc<dyn Transport>>,
+    retry: Option<RetryPolicy>,
+    timeout: Option<Duration>,
+    user_agent: Option<String>,
+    #[cfg(feature = "reqwest")]
+    http: HttpOptions,
+}
+
+/// 创建 reqwest 客户端的设置
+#[cfg(feature = "reqwest")]
+#[derive(Debug, Default)]
+struct HttpOptions {
+    connect_timeout: Option<Duration>,
+    pool_idle_timeout: Option<Duration>,
+    pool_max_idle_per_host: Option<usize>,
+    proxy: Option<String>,
+    root_certificates: Vec<Vec<u8>>,
+    tls_backend: TlsBackend,
+}
+
+impl ClientBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 直接指定 endpoint，优先于 account 和 region
+    pub fn endpoint(mut self, endpoint: &str) -> Self {
+        self.endpoint = Some(endpoint.trim_end_matches('/').to_string());
+        self
+    }
+
+    /// 阿里云账号 ID
+    pub fn account(mut self, account: &str) -> Self {
+        self.account = Some(account.to_string());
+        self
+    }
+
+    /// 地域 ID，例如 cn-hangzhou
+    pub fn region(mut self, region: &str) -> Self {
+        self.region = Some(region.to_string());
+        self
+    }
+
+    pub fn network(mut self, network: NetworkType) -> Self {
+        self.network = network;
+        self
+    }
+
+    pub fn credentials(self, id: &str, sec: &str) -> Self {
+        self.credentials_provider(StaticCredentials::new(id, sec))
+    }
+
+    pub fn credentials_provider<P: CredentialsProvider + 'static>(mut self, p: P) -> Self {
+        self.credentials = Some(Arc::new(p));
+        self
+    }
+
+    /// 使用自己的 [`Transport`]，此时连接相关的设置不生效
+    pub fn transport<T: Transport + 'static>(mut self, transport: T) -> Self {
+        self.transport = Some(Arc::new(transport));
+        self
+    }
+
+    pub fn retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// 默认的请求超时时间，见 [`Client::with_timeout`]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = Some(user_agent.to_string());
+        self
+    }
+
+    #[cfg(feature = "reqwest")]
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.http.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// 空闲连接保留的时间
+    #[cfg(feature = "reqwest")]
+    pub fn pool_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.http.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// 每个 host 最多保留的空闲连接数
+    #[cfg(feature = "reqwest")]
+    pub fn pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.http.pool_max_idle_per_host = Some(max);
+        self
+    }
+
+    /// HTTP 代理，例如 `http://127.0.0.1:8080`
+    #[cfg(feature = "reqwest")]
+    pub fn proxy(mut self, proxy: &str) -> Self {
+        self.http.proxy = Some(proxy.to_string());
+        self
+    }
+
+    /// 额外信任的 PEM 格式根证书
+    #[cfg(feature = "reqwest")]
+    pub fn add_root_certificate(mut self, pem: &[u8]) -> Self {
+        self.http.root_certificates.push(pem.to_vec());
+        self
+    }
+
+    #[cfg(feature = "reqwest")]
+    pub fn tls_backend(mut self, backend: TlsBackend) -> Self {
+        self.http.tls_backend = backend;
+        self
+    }
+
+    fn build_endpoint(&self) -> Result<String> {
+        if let Some(endpoint) = &self.endpoint {
+            return Ok(endpoint.clone());
+        }
+        match (&self.account, &self.region) {
+            (Some(account), Some(region)) => Ok(format!(
+                "https://{account}.mns.{region}{}.aliyuncs.com",
+                self.network.suffix()
+            )),
+            _ => Err(InvalidArgument(
+                "endpoint or account and region is required".to_string(),
+            )),
+        }
+    }
+
+    #[cfg(feature = "reqwest")]
+    fn build_reqwest(&self) -> Result<crate::transport::ReqwestTransport> {
+        let err = |e: reqwest::Error| InvalidArgument(e.to_string());
+        let opt = &self.http;
+        let mut b = reqwest::Client::builder();
+        b = match opt.tls_backend {
+            TlsBackend::Rustls => b.use_rustls_tls(),
+            #[cfg(feature = "native-tls")]
+            TlsBackend::NativeTls => b.use_native_tls(),
+        };
+        if let Some(t) = opt.connect_timeout {
+            b = b.connect_timeout(t);
+        }
+        if let Some(t) = opt.pool_idle_timeout {
+            b = b.pool_idle_timeout(t);
+        }
+        if let Some(n) = opt.pool_max_idle_per_host {
+            b = b.pool_max_idle_per_host(n);
+        }
+        if let Some(p) = &opt.proxy {
+            b = b.proxy(reqwest::Proxy::all(p).map_err(err)?);
+        }
+        for pem in &opt.root_certificates {
+            b = b.add_root_certificate(reqwest::Certificate::from_pem(pem).map_err(err)?);
+        }
+        Ok(crate::transport::ReqwestTransport::new(
+            b.build().map_err(err)?,
+        ))
+    }
+
+    pub fn build(self) -> Result<Client> {
+        let endpoint = self.build_endpoint()?;
+        let transport: Arc<dyn Transport> = match &self.transport {
+            Some(t) => t.clone(),
+            #[cfg(feature = "reqwest")]
+            None => Arc::new(self.build_reqwest()?),
+            #[cfg(not(feature = "reqwest"))]
+            None => {
+                return Err(InvalidArgument(
+                    "transport is required without the reqwest feature".to_string(),
+                ))
+            }
+        };
+        let credentials = self
+            .credentials
+            .unwrap_or_else(|| Arc::new(ChainCredentials::default_chain()));
+        let mut c = Client::from_parts(&endpoint, credentials, transport);
+        if let Some(retry) = self.retry {
+            c = c.with_retry_policy(retry);
+        }
+        if let Some(timeout) = self.timeout {
+            c = c.with_timeout(timeout);
+        }
+        if let Some(ua) = &self.user_agent {
+            c = c.with_user_agent(ua);
+        }
+        Ok(c)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::transport::{MemoryTransport, TransportResponse};
+    use http::StatusCode;
+
+    #[test]
+    fn test_endpoint() {
+        let b = ClientBuilder::new().account("123").region("cn-hangzhou");
+        assert_eq!(
+            "https://123.mns.cn-hangzhou.aliyuncs.com",
+            b.build_endpoint().unwrap()
+        );
+        let b = b.network(NetworkType::Internal);
+        assert_eq!(
+            "https://123.mns.cn-hangzhou-internal.aliyuncs.com",
+            b.build_endpoint().unwrap()
+        );
+        let b = b.network(NetworkType::Vpc);
+        assert_eq!(
+            "https://123.mns.cn-hangzhou-internal-vpc.aliyuncs.com",
+            b.build_endpoint().unwrap()
+        );
+        let b = b.endpoint("http://localhost:8080/");
+        assert_eq!("http://localhost:8080", b.build_endpoint().unwrap());
+        assert!(ClientBuilder::new()
+            .account("123")
+            .build_endpoint()
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_build() {
+        let t = MemoryTransport::new(|_| TransportResponse::new(StatusCode::OK, ""));
+        let c = Client::builder()
+            .account("123")
+            .region("cn-shanghai")
+            .credentials("id", "sec")
+            .transport(t.clone())
+            .timeout(Duration::from_secs(10))
+            .user_agent("test/1.0")
+            .build()
+            .unwrap();
+        c.request("/queues/q1", "GET", "application/xml", "", None)
+            .await
+            .unwrap();
+        let req = &t.requests()[0];
+        assert_eq!(
+            "https://123.mns.cn-shanghai.aliyuncs.com/queues/q1",
+            req.url
+        );
+        assert_eq!("test/1.0", req.headers["User-Agent"]);
+        assert_eq!(Duration::from_secs(10), req.timeout);
+
+        let c = Client::builder()
+            .endpoint("http://localhost")
+            .credentials("id", "sec")
+            .connect_timeout(Duration::from_secs(1))
+            .pool_idle_timeout(Duration::from_secs(30))
+            .pool_max_idle_per_host(4)
+            .proxy("http://127.0.0.1:3128")
+            .build()
+            .unwrap();
+        assert_eq!("http://localhost", c.endpoint());
+        assert!(Client::builder()
+            .endpoint("http://localhost")
+            .proxy("http://[::1")
+            .build()
+            .is_err());
+    }
+}
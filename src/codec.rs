@@ -0,0 +1,146 @@
+//! 消息正文的序列化方式，配合 [`crate::typed::TypedQueue`] 使用
+//! 每种格式由对应的 feature 开启：json、msgpack、cbor、protobuf
+//!
+//! MessagePack、CBOR、Protobuf 是二进制格式，队列需要使用 [`crate::queue::BodyEncoding::Base64`]
+#[cfg(any(
+    feature = "json",
+    feature = "msgpack",
+    feature = "cbor",
+    feature = "protobuf"
+))]
+use crate::error::Error::{DecodePayloadFailed, EncodePayloadFailed};
+use crate::error::Result;
+#[cfg(any(feature = "json", feature = "msgpack", feature = "cbor"))]
+use serde::{de::DeserializeOwned, Serialize};
+
+/// 将 T 与消息正文互相转换
+pub trait Codec<T> {
+    fn encode(value: &T) -> Result<Vec<u8>>;
+    fn decode(data: &[u8]) -> Result<T>;
+}
+
+/// JSON，文本格式，Raw 编码的队列也可以使用
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonCodec;
+
+#[cfg(feature = "json")]
+impl<T: Serialize + DeserializeOwned> Codec<T> for JsonCodec {
+    fn encode(value: &T) -> Result<Vec<u8>> {
+        serde_json::to_vec(value).map_err(|e| EncodePayloadFailed(e.to_string()))
+    }
+
+    fn decode(data: &[u8]) -> Result<T> {
+        serde_json::from_slice(data).map_err(|e| DecodePayloadFailed(e.to_string()))
+    }
+}
+
+/// MessagePack，结构体按字段名编码
+#[cfg(feature = "msgpack")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MsgPackCodec;
+
+#[cfg(feature = "msgpack")]
+impl<T: Serialize + DeserializeOwned> Codec<T> for MsgPackCodec {
+    fn encode(value: &T) -> Result<Vec<u8>> {
+        rmp_serde::to_vec_named(value).map_err(|e| EncodePayloadFailed(e.to_string()))
+    }
+
+    fn decode(data: &[u8]) -> Result<T> {
+        rmp_serde::from_slice(data).map_err(|e| DecodePayloadFailed(e.to_string()))
+    }
+}
+
+#[cfg(feature = "cbor")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CborCodec;
+
+#[cfg(feature = "cbor")]
+impl<T: Serialize + DeserializeOwned> Codec<T> for CborCodec {
+    fn encode(value: &T) -> Result<Vec<u8>> {
+        let mut buf = vec![];
+        ciborium::into_writer(value, &mut buf).map_err(|e| EncodePayloadFailed(e.to_string()))?;
+        Ok(buf)
+    }
+
+    fn decode(data: &[u8]) -> Result<T> {
+        ciborium::from_reader(data).map_err(|e| DecodePayloadFailed(e.to_string()))
+    }
+}
+
+/// Protobuf，T 为 prost 生成的消息类型
+#[cfg(feature = "protobuf")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProstCodec;
+
+#[cfg(feature = "protobuf")]
+impl<T: prost::Message + Default> Codec<T> for ProstCodec {
+    fn encode(value: &T) -> Result<Vec<u8>> {
+        Ok(value.encode_to_vec())
+    }
+
+    fn decode(data: &[u8]) -> Result<T> {
+        T::decode(data).map_err(|e| DecodePayloadFailed(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[allow(unused_imports)]
+    use super::*;
+
+    #[cfg(any(feature = "json", feature = "msgpack", feature = "cbor"))]
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Order {
+        id: u64,
+        items: Vec<String>,
+    }
+
+    #[cfg(any(feature = "json", feature = "msgpack", feature = "cbor"))]
+    fn round_trip<C: Codec<Order>>() {
+        let o = Order {
+            id: 1,
+            items: vec!["a".to_string(), "b".to_string()],
+        };
+        assert_eq!(o, C::decode(&C::encode(&o).unwrap()).unwrap());
+        assert!(C::decode(b"\xff\xff").is_err());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json() {
+        round_trip::<JsonCodec>();
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn test_msgpack() {
+        round_trip::<MsgPackCodec>();
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn test_cbor() {
+        round_trip::<CborCodec>();
+    }
+
+    #[cfg(feature = "protobuf")]
+    #[test]
+    fn test_prost() {
+        #[derive(Clone, PartialEq, prost::Message)]
+        struct Order {
+            #[prost(uint64, tag = "1")]
+            id: u64,
+            #[prost(string, repeated, tag = "2")]
+            items: Vec<String>,
+        }
+        let o = Order {
+            id: 1,
+            items: vec!["a".to_string()],
+        };
+        assert_eq!(
+            o,
+            ProstCodec::decode(&ProstCodec::encode(&o).unwrap()).unwrap()
+        );
+    }
+}
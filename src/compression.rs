@@ -0,0 +1,168 @@
+//! 消息正文压缩
+//! 压缩后的正文以 4 字节的头部开始：`\0MZ` 加上压缩算法编号，没有头部的正文原样返回，
+//! 因此压缩与未压缩的消息可以在同一个队列中共存
+//!
+//! 压缩后是二进制数据，队列需要使用 [`crate::queue::BodyEncoding::Base64`]
+use crate::error::Error::{CompressFailed, DecompressFailed};
+use crate::error::Result;
+
+const MAGIC: &[u8] = b"\0MZ";
+const GZIP: u8 = 1;
+const ZSTD: u8 = 2;
+
+/// 解压后正文的默认上限，队列消息最大 64KB，这里取 16 倍
+pub const DEFAULT_MAX_DECOMPRESSED_SIZE: usize = 16 * 64 * 1024;
+
+/// 压缩算法，由对应的 feature 开启
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    #[cfg(feature = "gzip")]
+    Gzip,
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+impl Compression {
+    fn id(&self) -> u8 {
+        match *self {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => GZIP,
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => ZSTD,
+        }
+    }
+
+    #[cfg_attr(not(any(feature = "gzip", feature = "zstd")), allow(unused_variables))]
+    fn compress(&self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        match *self {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => {
+                use std::io::Write;
+                let mut w = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+                w.write_all(data)?;
+                w.finish()
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => zstd::stream::encode_all(data, 0),
+        }
+    }
+}
+
+/// 压缩设置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionOptions {
+    pub algorithm: Compression,
+    /// 正文小于该字节数时不压缩
+    pub threshold: usize,
+}
+
+impl CompressionOptions {
+    /// 超过阈值且压缩后更小时才使用压缩结果
+    pub(crate) fn compress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        if data.len() < self.threshold {
+            return Ok(data.to_vec());
+        }
+        let compressed = self
+            .algorithm
+            .compress(data)
+            .map_err(|e| CompressFailed(e.to_string()))?;
+        if compressed.len() + MAGIC.len() + 1 >= data.len() {
+            return Ok(data.to_vec());
+        }
+        let mut out = Vec::with_capacity(compressed.len() + MAGIC.len() + 1);
+        out.extend_from_slice(MAGIC);
+        out.push(self.algorithm.id());
+        out.extend_from_slice(&compressed);
+        Ok(out)
+    }
+}
+
+/// 按头部解压，没有头部时原样返回
+/// 解压后超过 limit 字节时返回 [`DecompressFailed`]，避免压缩炸弹占满内存
+#[cfg_attr(not(any(feature = "gzip", feature = "zstd")), allow(unused_variables))]
+pub(crate) fn decompress(data: Vec<u8>, limit: usize) -> Result<Vec<u8>> {
+    if !data.starts_with(MAGIC) || data.len() <= MAGIC.len() {
+        return Ok(data);
+    }
+    let body = &data[MAGIC.len() + 1..];
+    match data[MAGIC.len()] {
+        #[cfg(feature = "gzip")]
+        GZIP => read_limited(flate2::read::GzDecoder::new(body), limit),
+        #[cfg(feature = "zstd")]
+        ZSTD => read_limited(
+            zstd::stream::read::Decoder::new(body).map_err(|e| DecompressFailed(e.to_string()))?,
+            limit,
+        ),
+        #[cfg(not(feature = "gzip"))]
+        GZIP => Err(DecompressFailed("gzip feature is not enabled".to_string())),
+        #[cfg(not(feature = "zstd"))]
+        ZSTD => Err(DecompressFailed("zstd feature is not enabled".to_string())),
+        id => Err(DecompressFailed(format!("unknown compression {id}"))),
+    }
+}
+
+/// 最多读取 limit + 1 字节，多出的一个字节说明超过了上限
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+fn read_limited<R: std::io::Read>(r: R, limit: usize) -> Result<Vec<u8>> {
+    use std::io::Read;
+    let mut out = vec![];
+    r.take(limit as u64 + 1)
+        .read_to_end(&mut out)
+        .map_err(|e| DecompressFailed(e.to_string()))?;
+    if out.len() > limit {
+        return Err(DecompressFailed(format!(
+            "decompressed body exceeds {limit} bytes"
+        )));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_uncompressed() {
+        assert_eq!(
+            b"hello".to_vec(),
+            decompress(b"hello".to_vec(), DEFAULT_MAX_DECOMPRESSED_SIZE).unwrap()
+        );
+        assert!(decompress(b"\0MZ\x09xx".to_vec(), DEFAULT_MAX_DECOMPRESSED_SIZE).is_err());
+    }
+
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    fn round_trip(algorithm: Compression) {
+        let opt = CompressionOptions {
+            algorithm,
+            threshold: 64,
+        };
+        assert_eq!(b"short".to_vec(), opt.compress(b"short").unwrap());
+
+        let data = "{\"k\":\"v\"}".repeat(100).into_bytes();
+        let compressed = opt.compress(&data).unwrap();
+        assert!(compressed.starts_with(MAGIC));
+        assert!(compressed.len() < data.len());
+        assert_eq!(data, decompress(compressed.clone(), data.len()).unwrap());
+        assert!(matches!(
+            decompress(compressed, data.len() - 1),
+            Err(DecompressFailed(_))
+        ));
+
+        // 高度重复的正文压缩后很小，解压时按上限截断
+        let bomb = opt.compress(&vec![0; 4 << 20]).unwrap();
+        assert!(bomb.len() < 64 * 1024);
+        assert!(decompress(bomb, DEFAULT_MAX_DECOMPRESSED_SIZE).is_err());
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_gzip() {
+        round_trip(Compression::Gzip);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zstd() {
+        round_trip(Compression::Zstd);
+    }
+}
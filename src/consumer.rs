@@ -21,10 +21,15 @@
 //!     consumer.run();
 //! }
 //! ```
+use crate::notification::NotificationBody;
 pub use crate::options::ConsumeOptions;
 use crate::queue::QueueOperation;
+#[cfg(feature = "tokio")]
+use crate::queue::BATCH_MAX_MESSAGES;
+use crate::subscription::NotifyContentFormat;
 use crate::Queue;
 use anyhow::Result;
+use std::borrow::Cow;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
@@ -34,23 +39,69 @@ use tracing::warn;
 
 pub type DeliveryResult = Result<Option<Delivery>>;
 
+/// 消息正文解码失败（例如解密失败），以 [`anyhow::Error`] 交给 delegate
+/// 可以用 `downcast_ref::<UndecodableDelivery>()` 取回原消息，再 ack 或 reject
+#[derive(Debug, thiserror::Error)]
+#[error("decode message failed: {error}")]
+pub struct UndecodableDelivery {
+    /// 原消息，data 为空
+    pub delivery: Delivery,
+    #[source]
+    pub error: crate::error::Error,
+}
+
+/// 拉取消息失败后等待多久再重试
+#[cfg(feature = "tokio")]
+const RETRY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
 #[derive(Debug, Clone)]
 pub struct Delivery {
+    /// 解码后的消息正文，claim check 消息为空，需要使用 [`Delivery::payload`]
     pub data: Vec<u8>,
+    blob_key: Option<String>,
     receipt_handle: String,
     next_visible_time: i64,
+    dequeue_count: i64,
     queue: Queue,
     // WIP
     // auto_ack: bool,
 }
 
 impl Delivery {
+    /// 主题推送到队列的消息，按订阅的 NotifyContentFormat 拆开通知信封
+    pub fn notification(
+        &self,
+        format: NotifyContentFormat,
+    ) -> crate::error::Result<NotificationBody> {
+        NotificationBody::decode(&self.data, format)
+    }
+    /// 消息被消费的次数
+    pub fn dequeue_count(&self) -> i64 {
+        self.dequeue_count
+    }
+    /// 消息正文是否保存在 BlobStore 中
+    pub fn is_claim_check(&self) -> bool {
+        self.blob_key.is_some()
+    }
+    /// 消息正文，claim check 消息在调用时才从 BlobStore 读取
+    pub async fn payload(&self) -> crate::error::Result<Cow<'_, [u8]>> {
+        match &self.blob_key {
+            Some(key) => Ok(Cow::Owned(self.queue.load_blob(key).await?)),
+            None => Ok(Cow::Borrowed(&self.data)),
+        }
+    }
     pub async fn ack(&self) -> Result<()> {
         // delete
-        Ok(self
-            .queue
+        self.queue
             .delete_message(self.receipt_handle.as_str())
-            .await?)
+            .await?;
+        // 消息已经删除，blob 删除失败只记录日志
+        if let Some(key) = &self.blob_key {
+            if let Err(e) = self.queue.delete_blob(key).await {
+                warn!("delete blob {key} of acked message failed, {:?}", e);
+            }
+        }
+        Ok(())
     }
     pub async fn reject(&self) -> Result<()> {
         // change visibility
@@ -121,29 +172,56 @@ impl Consumer {
             let semaphore = Arc::new(Semaphore::new(c.options.prefetch_count as usize));
             loop {
                 let c = c.clone();
-                let permit = semaphore.clone().acquire_owned().await.unwrap();
-                let m = match c.queue.receive_message(Some(30)).await {
-                    Ok(m) => m,
+                // 至少等到一个空闲名额，再尽量多取几个，一次拉取多条消息
+                let mut permits = vec![semaphore.clone().acquire_owned().await.unwrap()];
+                while permits.len() < BATCH_MAX_MESSAGES {
+                    match semaphore.clone().try_acquire_owned() {
+                        Ok(p) => permits.push(p),
+                        Err(_) => break,
+                    }
+                }
+                let ms = match c
+                    .queue
+                    .batch_receive_raw(permits.len() as u8, Some(30))
+                    .await
+                {
+                    Ok(ms) => ms,
                     Err(e) => {
+                        // 队列为空时返回空数组，这里只会是网络或服务端错误，稍后重试
                         warn!("receive message error, {:?}", e);
-                        return;
+                        drop(permits);
+                        tokio::time::sleep(RETRY_INTERVAL).await;
+                        continue;
                     }
                 };
 
-                let d = Delivery {
-                    data: m.message_body.into_bytes(),
-                    receipt_handle: m.receipt_handle,
-                    next_visible_time: m.next_visible_time,
-                    queue: c.queue.clone(),
-                    // auto_ack: c.options.auto_ack,
-                };
                 let inner = c.inner.lock().await;
-                if let Some(delegate) = inner.delegate.as_ref() {
-                    let delegate = delegate.clone();
-                    tokio::spawn(async move {
-                        let _permit = permit;
-                        delegate.on_new_delivery(Ok(Some(d))).await;
-                    });
+                for (m, permit) in ms.into_iter().zip(permits) {
+                    let mut d = Delivery {
+                        data: vec![],
+                        blob_key: None,
+                        receipt_handle: m.receipt_handle.clone(),
+                        next_visible_time: m.next_visible_time,
+                        dequeue_count: m.dequeue_count,
+                        queue: c.queue.clone(),
+                        // auto_ack: c.options.auto_ack,
+                    };
+                    // 解码失败（例如解密失败）的消息连同原消息以错误交给 delegate，不影响同批的其他消息
+                    let d: DeliveryResult = match c.queue.decode_lazy(m) {
+                        Ok((m, blob_key)) => {
+                            d.data = m.data;
+                            d.blob_key = blob_key;
+                            Ok(Some(d))
+                        }
+                        Err(error) => Err(UndecodableDelivery { delivery: d, error }.into()),
+                    };
+                    if let Some(delegate) = inner.delegate.as_ref() {
+                        let delegate = delegate.clone();
+                        tokio::spawn(async move {
+                            let _permit = permit;
+                            delegate.on_new_delivery(d).await;
+                        });
+                    }
                 }
             }
         });
@@ -163,3 +241,105 @@ pub enum ConsumerState {
     Canceling,
     Canceled,
 }
+
+#[cfg(all(test, feature = "tokio"))]
+mod test {
+    use super::*;
+    use crate::error::Error::DecodeMessageBodyFailed;
+    use crate::queue::BodyEncoding;
+    use crate::retry::RetryPolicy;
+    use crate::transport::{MemoryTransport, TransportResponse};
+    use crate::Client;
+    use http::{Method, StatusCode};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_undecodable_delivery() {
+        let n = AtomicUsize::new(0);
+        let t = MemoryTransport::new(move |req| {
+            if req.method == Method::PUT {
+                return TransportResponse::new(
+                    StatusCode::OK,
+                    "<ChangeVisibility><ReceiptHandle>h-2</ReceiptHandle>\
+                     <NextVisibleTime>0</NextVisibleTime></ChangeVisibility>",
+                );
+            }
+            if n.fetch_add(1, Ordering::SeqCst) == 0 {
+                TransportResponse::new(
+                    StatusCode::OK,
+                    "<Messages><Message><MessageId>1</MessageId><ReceiptHandle>h-1</ReceiptHandle>\
+                     <MessageBodyMD5>0</MessageBodyMD5><MessageBody>not base64!</MessageBody>\
+                     <EnqueueTime>0</EnqueueTime><NextVisibleTime>0</NextVisibleTime>\
+                     <FirstDequeueTime>0</FirstDequeueTime><DequeueCount>4</DequeueCount>\
+                     <Priority>8</Priority></Message></Messages>",
+                )
+            } else {
+                TransportResponse::new(StatusCode::SERVICE_UNAVAILABLE, "unavailable")
+            }
+        });
+        let c = Client::with_transport("http://mns.test", "id", "sec", t.clone());
+        let q = Queue::new("q1", &c)
+            .with_encoding(BodyEncoding::Base64)
+            .with_retry_policy(RetryPolicy::none());
+        let consumer = Consumer::new(q, ConsumeOptions::default());
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        consumer
+            .set_delegate(move |msg: DeliveryResult| {
+                let tx = tx.clone();
+                async move {
+                    let u = msg.unwrap_err().downcast::<UndecodableDelivery>().unwrap();
+                    assert!(matches!(u.error, DecodeMessageBodyFailed(_)));
+                    assert_eq!(4, u.delivery.dequeue_count());
+                    u.delivery.reject().await.unwrap();
+                    tx.send(()).unwrap();
+                }
+            })
+            .await;
+        consumer.run();
+
+        rx.recv().await.unwrap();
+        let req = t
+            .requests()
+            .into_iter()
+            .find(|r| r.method == Method::PUT)
+            .unwrap();
+        assert!(req.url.contains("ReceiptHandle=h-1&"), "{}", req.url);
+    }
+
+    #[derive(Debug)]
+    struct FailingStore;
+
+    #[async_trait::async_trait]
+    impl crate::claim_check::BlobStore for FailingStore {
+        async fn put(&self, _: &[u8]) -> crate::error::Result<String> {
+            Ok("k1".to_string())
+        }
+        async fn get(&self, _: &str) -> crate::error::Result<Vec<u8>> {
+            Ok(Vec::new())
+        }
+        async fn delete(&self, key: &str) -> crate::error::Result<()> {
+            Err(crate::error::Error::BlobStoreFailed(key.to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ack_blob_delete_failure() {
+        use crate::claim_check::ClaimCheckOptions;
+
+        let t = MemoryTransport::new(|_| TransportResponse::new(StatusCode::NO_CONTENT, ""));
+        let c = Client::with_transport("http://mns.test", "id", "sec", t.clone());
+        let q = Queue::new("q1", &c)
+            .with_claim_check(ClaimCheckOptions::new(FailingStore, 8).delete_on_ack(b"secret"));
+        let d = Delivery {
+            data: Vec::new(),
+            blob_key: Some("k1".to_string()),
+            receipt_handle: "h-1".to_string(),
+            next_visible_time: 0,
+            dequeue_count: 1,
+            queue: q,
+        };
+        // 消息已经删除，blob 删除失败不影响 ack 的结果
+        d.ack().await.unwrap();
+        assert_eq!(Method::DELETE, t.requests()[0].method);
+    }
+}
@@ -0,0 +1,561 @@
+//! 访问凭证，[`crate::Client`] 每次请求前都会向 [`CredentialsProvider`] 获取凭证
+//!
+//! 内置的 provider：
+//! - [`StaticCredentials`]：固定的 AccessKey，可以带 STS SecurityToken
+//! - [`EnvCredentials`]：环境变量 `ALIBABA_CLOUD_ACCESS_KEY_ID`、`ALIBABA_CLOUD_ACCESS_KEY_SECRET`、`ALIBABA_CLOUD_SECURITY_TOKEN`
+//! - [`ProfileCredentials`]：aliyun cli 的配置文件 `~/.aliyun/config.json`
+//! - [`EcsRamRoleCredentials`]：ECS 实例元数据中的 RAM 角色临时凭证，过期前自动刷新
+//! - [`ChainCredentials`]：依次尝试多个 provider，[`ChainCredentials::default_chain`] 为默认的查找顺序
+//!
+//! # Example
+//! ```rust,no_run
+//! use mns::credentials::ChainCredentials;
+//! use mns::transport::ReqwestTransport;
+//! use mns::Client;
+//!
+//! let client = Client::with_credentials(
+//!     "https://xxx.mns.cn-hangzhou.aliyuncs.com",
+//!     ChainCredentials::default_chain(),
+//!     ReqwestTransport::default(),
+//! );
+//! ```
+use crate::error::Error::LoadCredentialsFailed;
+use crate::error::Result;
+#[cfg(feature = "tokio")]
+use crate::transport::{Transport, TransportRequest};
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::fmt::Debug;
+use std::path::PathBuf;
+#[cfg(feature = "tokio")]
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use time::OffsetDateTime;
+
+/// 一组访问凭证
+#[derive(Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub access_key_id: String,
+    pub access_key_secret: String,
+    /// STS 临时凭证的 SecurityToken
+    pub security_token: Option<String>,
+    /// 临时凭证的过期时间，None 表示不会过期
+    pub expiration: Option<OffsetDateTime>,
+}
+
+impl Credentials {
+    pub fn new(access_key_id: &str, access_key_secret: &str) -> Self {
+        Self {
+            access_key_id: access_key_id.to_string(),
+            access_key_secret: access_key_secret.to_string(),
+            security_token: None,
+            expiration: None,
+        }
+    }
+
+    pub fn with_security_token(mut self, token: &str) -> Self {
+        self.security_token = Some(token.to_string());
+        self
+    }
+
+    /// 在 d 之内是否会过期
+    pub fn expires_within(&self, d: time::Duration) -> bool {
+        self.expiration
+            .is_some_and(|e| e <= OffsetDateTime::now_utc() + d)
+    }
+}
+
+impl Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Credentials")
+            .field("access_key_id", &self.access_key_id)
+            .field("expiration", &self.expiration)
+            .finish_non_exhaustive()
+    }
+}
+
+/// 提供访问凭证
+#[async_trait]
+pub trait CredentialsProvider: Debug + Send + Sync {
+    async fn credentials(&self) -> Result<Credentials>;
+}
+
+/// 固定的凭证
+#[derive(Debug, Clone)]
+pub struct StaticCredentials(Credentials);
+
+impl StaticCredentials {
+    pub fn new(access_key_id: &str, access_key_secret: &str) -> Self {
+        Self(Credentials::new(access_key_id, access_key_secret))
+    }
+
+    pub fn with_security_token(self, token: &str) -> Self {
+        Self(self.0.with_security_token(token))
+    }
+}
+
+impl From<Credentials> for StaticCredentials {
+    fn from(c: Credentials) -> Self {
+        Self(c)
+    }
+}
+
+#[async_trait]
+impl CredentialsProvider for StaticCredentials {
+    async fn credentials(&self) -> Result<Credentials> {
+        Ok(self.0.clone())
+    }
+}
+
+/// 从环境变量读取凭证
+#[derive(Debug, Clone, Default)]
+pub struct EnvCredentials;
+
+impl EnvCredentials {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn load<F: Fn(&str) -> Option<String>>(var: F) -> Result<Credentials> {
+        let get = |k: &str| var(k).filter(|v| !v.is_empty());
+        let (Some(id), Some(sec)) = (
+            get("ALIBABA_CLOUD_ACCESS_KEY_ID"),
+            get("ALIBABA_CLOUD_ACCESS_KEY_SECRET"),
+        ) else {
+            return Err(LoadCredentialsFailed(
+                "ALIBABA_CLOUD_ACCESS_KEY_ID or ALIBABA_CLOUD_ACCESS_KEY_SECRET is not set"
+                    .to_string(),
+            ));
+        };
+        Ok(Credentials {
+            security_token: get("ALIBABA_CLOUD_SECURITY_TOKEN"),
+            ..Credentials::new(&id, &sec)
+        })
+    }
+}
+
+#[async_trait]
+impl CredentialsProvider for EnvCredentials {
+    async fn credentials(&self) -> Result<Credentials> {
+        Self::load(|k| std::env::var(k).ok())
+    }
+}
+
+/// aliyun cli 的配置文件
+#[derive(Debug, Deserialize)]
+struct ProfileFile {
+    #[serde(default)]
+    current: String,
+    #[serde(default)]
+    profiles: Vec<Profile>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Profile {
+    name: String,
+    #[serde(default)]
+    mode: String,
+    #[serde(default)]
+    access_key_id: String,
+    #[serde(default)]
+    access_key_secret: String,
+    #[serde(default)]
+    sts_token: String,
+}
+
+/// 从 aliyun cli 的配置文件读取凭证，支持 AK 和 StsToken 两种模式
+/// 默认读取 `~/.aliyun/config.json` 中由环境变量 `ALIBABA_CLOUD_PROFILE` 或 `current` 指定的 profile
+/// 读取成功后缓存，修改配置文件需要重新创建
+#[derive(Debug, Clone, Default)]
+pub struct ProfileCredentials {
+    path: Option<PathBuf>,
+    profile: Option<String>,
+    cached: Arc<Mutex<Option<Credentials>>>,
+}
+
+impl ProfileCredentials {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    pub fn with_profile(mut self, profile: &str) -> Self {
+        self.profile = Some(profile.to_string());
+        self
+    }
+
+    fn path(&self) -> Result<PathBuf> {
+        if let Some(p) = &self.path {
+            return Ok(p.clone());
+        }
+        std::env::var_os("HOME")
+            .or_else(|| std::env::var_os("USERPROFILE"))
+            .map(|h| PathBuf::from(h).join(".aliyun").join("config.json"))
+            .ok_or_else(|| LoadCredentialsFailed("home directory not found".to_string()))
+    }
+
+    fn parse(&self, content: &str) -> Result<Credentials> {
+        let f: ProfileFile = serde_json::from_str(content)
+            .map_err(|e| LoadCredentialsFailed(format!("invalid profile file: {e}")))?;
+        let name = self
+            .profile
+            .clone()
+            .or_else(|| std::env::var("ALIBABA_CLOUD_PROFILE").ok())
+            .unwrap_or(f.current);
+        let name = if name.is_empty() { "default" } else { &name };
+        let p = f
+            .profiles
+            .iter()
+            .find(|p| p.name == name)
+            .ok_or_else(|| LoadCredentialsFailed(format!("profile {name} not found")))?;
+        match p.mode.as_str() {
+            "AK" | "" => Ok(Credentials::new(&p.access_key_id, &p.access_key_secret)),
+            "StsToken" => Ok(Credentials::new(&p.access_key_id, &p.access_key_secret)
+                .with_security_token(&p.sts_token)),
+            mode => Err(LoadCredentialsFailed(format!(
+                "profile {name} uses unsupported mode {mode}"
+            ))),
+        }
+    }
+}
+
+#[async_trait]
+impl CredentialsProvider for ProfileCredentials {
+    async fn credentials(&self) -> Result<Credentials> {
+        if let Some(c) = self.cached.lock().unwrap().clone() {
+            return Ok(c);
+        }
+        let path = self.path()?;
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| LoadCredentialsFailed(format!("{}: {e}", path.display())))?;
+        let c = self.parse(&content)?;
+        *self.cached.lock().unwrap() = Some(c.clone());
+        Ok(c)
+    }
+}
+
+/// ECS 实例元数据服务的地址
+#[cfg(feature = "tokio")]
+pub const ECS_METADATA_URL: &str = "http://100.100.100.200";
+
+/// 过期前多久开始刷新
+#[cfg(feature = "tokio")]
+const REFRESH_AHEAD: time::Duration = time::Duration::minutes(5);
+
+/// ECS 实例 RAM 角色的临时凭证
+/// 凭证缓存在内存中，过期前 5 分钟在后台刷新，刷新期间继续使用旧凭证，请求不会被阻塞
+#[cfg(feature = "tokio")]
+#[derive(Debug, Clone)]
+pub struct EcsRamRoleCredentials {
+    base_url: String,
+    role_name: Option<String>,
+    transport: Arc<dyn Transport>,
+    cache: Arc<Cache>,
+}
+
+#[cfg(feature = "tokio")]
+#[derive(Debug, Default)]
+struct Cache {
+    credentials: std::sync::RwLock<Option<Credentials>>,
+    refreshing: AtomicBool,
+    refresh_lock: tokio::sync::Mutex<()>,
+}
+
+#[cfg(feature = "tokio")]
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct EcsCredentialsResponse {
+    #[serde(default)]
+    code: String,
+    access_key_id: String,
+    access_key_secret: String,
+    security_token: String,
+    #[serde(with = "time::serde::rfc3339")]
+    expiration: OffsetDateTime,
+}
+
+#[cfg(feature = "tokio")]
+impl EcsRamRoleCredentials {
+    /// role_name 为 None 时从元数据服务查询实例绑定的角色
+    #[cfg(feature = "reqwest")]
+    pub fn new(role_name: Option<&str>) -> Self {
+        Self::with_transport(role_name, crate::transport::ReqwestTransport::default())
+    }
+
+    pub fn with_transport<T: Transport + 'static>(role_name: Option<&str>, transport: T) -> Self {
+        Self {
+            base_url: ECS_METADATA_URL.to_string(),
+            role_name: role_name.map(str::to_string),
+            transport: Arc::new(transport),
+            cache: Arc::default(),
+        }
+    }
+
+    /// 修改元数据服务的地址，例如在测试中指向本地的模拟服务
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self.cache = Arc::default();
+        self
+    }
+
+    async fn get(&self, path: &str) -> Result<String> {
+        let res = self
+            .transport
+            .send(TransportRequest {
+                method: http::Method::GET,
+                url: format!(
+                    "{}/latest/meta-data/ram/security-credentials/{path}",
+                    self.base_url
+                ),
+                headers: Default::default(),
+                body: vec![],
+                timeout: std::time::Duration::from_secs(5),
+            })
+            .await
+            .map_err(|e| LoadCredentialsFailed(format!("ecs metadata: {e}")))?;
+        let body = String::from_utf8_lossy(&res.body).trim().to_string();
+        if !res.status.is_success() {
+            return Err(LoadCredentialsFailed(format!(
+                "ecs metadata returned {}: {body}",
+                res.status
+            )));
+        }
+        Ok(body)
+    }
+
+    async fn fetch(&self) -> Result<Credentials> {
+        let role = match &self.role_name {
+            Some(r) => r.clone(),
+            None => self.get("").await?,
+        };
+        let r: EcsCredentialsResponse = serde_json::from_str(&self.get(&role).await?)
+            .map_err(|e| LoadCredentialsFailed(format!("ecs metadata: {e}")))?;
+        if !r.code.is_empty() && r.code != "Success" {
+            return Err(LoadCredentialsFailed(format!("ecs metadata: {}", r.code)));
+        }
+        Ok(Credentials {
+            expiration: Some(r.expiration),
+            ..Credentials::new(&r.access_key_id, &r.access_key_secret)
+                .with_security_token(&r.security_token)
+        })
+    }
+
+    fn cached(&self) -> Option<Credentials> {
+        self.cache.credentials.read().unwrap().clone()
+    }
+
+    /// 同一时间只有一个刷新请求，等待锁期间其他请求已经刷新过时直接返回
+    async fn refresh(&self) -> Result<Credentials> {
+        let _guard = self.cache.refresh_lock.lock().await;
+        if let Some(c) = self.cached().filter(|c| !c.expires_within(REFRESH_AHEAD)) {
+            return Ok(c);
+        }
+        let c = self.fetch().await?;
+        *self.cache.credentials.write().unwrap() = Some(c.clone());
+        Ok(c)
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[async_trait]
+impl CredentialsProvider for EcsRamRoleCredentials {
+    async fn credentials(&self) -> Result<Credentials> {
+        match self.cached() {
+            Some(c) if !c.expires_within(REFRESH_AHEAD) => Ok(c),
+            // 即将过期，后台刷新，本次仍使用旧凭证
+            Some(c) if !c.expires_within(time::Duration::ZERO) => {
+                if !self.cache.refreshing.swap(true, Ordering::AcqRel) {
+                    let p = self.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = p.refresh().await {
+                            tracing::warn!("refresh ecs ram role credentials failed, {e}");
+                        }
+                        p.cache.refreshing.store(false, Ordering::Release);
+                    });
+                }
+                Ok(c)
+            }
+            _ => self.refresh().await,
+        }
+    }
+}
+
+/// 依次尝试多个 provider，使用第一个成功返回的凭证，之后优先使用该 provider
+#[derive(Debug)]
+pub struct ChainCredentials {
+    providers: Vec<Box<dyn CredentialsProvider>>,
+    last: AtomicUsize,
+}
+
+impl ChainCredentials {
+    pub fn new(providers: Vec<Box<dyn CredentialsProvider>>) -> Self {
+        Self {
+            providers,
+            last: AtomicUsize::new(0),
+        }
+    }
+
+    /// 环境变量、配置文件，设置了环境变量 `ALIBABA_CLOUD_ECS_METADATA`（RAM 角色名）时再尝试 ECS 实例元数据
+    pub fn default_chain() -> Self {
+        #[allow(unused_mut)]
+        let mut providers: Vec<Box<dyn CredentialsProvider>> = vec![
+            Box::new(EnvCredentials::new()),
+            Box::new(ProfileCredentials::new()),
+        ];
+        #[cfg(all(feature = "tokio", feature = "reqwest"))]
+        if let Some(role) = std::env::var("ALIBABA_CLOUD_ECS_METADATA")
+            .ok()
+            .filter(|r| !r.is_empty())
+        {
+            providers.push(Box::new(EcsRamRoleCredentials::new(Some(&role))));
+        }
+        Self::new(providers)
+    }
+}
+
+#[async_trait]
+impl CredentialsProvider for ChainCredentials {
+    async fn credentials(&self) -> Result<Credentials> {
+        let last = self.last.load(Ordering::Relaxed);
+        if let Some(p) = self.providers.get(last) {
+            if let Ok(c) = p.credentials().await {
+                return Ok(c);
+            }
+        }
+        let mut errors = vec![];
+        for (i, p) in self.providers.iter().enumerate() {
+            match p.credentials().await {
+                Ok(c) => {
+                    self.last.store(i, Ordering::Relaxed);
+                    return Ok(c);
+                }
+                Err(e) => errors.push(e.to_string()),
+            }
+        }
+        Err(LoadCredentialsFailed(format!(
+            "no credentials found: [{}]",
+            errors.join("; ")
+        )))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::transport::{MemoryTransport, TransportResponse};
+    use http::StatusCode;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_env() {
+        let vars = HashMap::from([
+            ("ALIBABA_CLOUD_ACCESS_KEY_ID", "id"),
+            ("ALIBABA_CLOUD_ACCESS_KEY_SECRET", "sec"),
+        ]);
+        let c = EnvCredentials::load(|k| vars.get(k).map(|v| v.to_string())).unwrap();
+        assert_eq!(Credentials::new("id", "sec"), c);
+        assert!(EnvCredentials::load(|_| None).is_err());
+        assert!(!format!("{c:?}").contains("sec"));
+    }
+
+    #[test]
+    fn test_profile() {
+        let content = r#"{
+  "current": "sts",
+  "profiles": [
+    {"name": "default", "mode": "AK", "access_key_id": "id1", "access_key_secret": "sec1", "region_id": "cn-hangzhou"},
+    {"name": "sts", "mode": "StsToken", "access_key_id": "id2", "access_key_secret": "sec2", "sts_token": "token"},
+    {"name": "role", "mode": "RamRoleArn", "access_key_id": "id3", "access_key_secret": "sec3"}
+  ]
+}"#;
+        let p = ProfileCredentials::new().with_profile("default");
+        assert_eq!(Credentials::new("id1", "sec1"), p.parse(content).unwrap());
+        let p = ProfileCredentials::new().with_profile("sts");
+        assert_eq!(
+            Credentials::new("id2", "sec2").with_security_token("token"),
+            p.parse(content).unwrap()
+        );
+        assert!(p.with_profile("role").parse(content).is_err());
+        assert!(ProfileCredentials::new()
+            .with_profile("missing")
+            .parse(content)
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ecs_ram_role() {
+        let n = Arc::new(AtomicUsize::new(0));
+        let count = n.clone();
+        let t = MemoryTransport::new(move |req| {
+            match req.url.as_str() {
+                "http://127.0.0.1:8080/latest/meta-data/ram/security-credentials/" => {
+                    TransportResponse::new(StatusCode::OK, "role1")
+                }
+                "http://127.0.0.1:8080/latest/meta-data/ram/security-credentials/role1" => {
+                    // 第一次返回 1 分钟后过期的凭证，之后返回 1 小时后过期的凭证
+                    let expiration = OffsetDateTime::now_utc()
+                        + if count.fetch_add(1, Ordering::SeqCst) == 0 {
+                            time::Duration::minutes(1)
+                        } else {
+                            time::Duration::hours(1)
+                        };
+                    TransportResponse::new(
+                        StatusCode::OK,
+                        format!(
+                            r#"{{"AccessKeyId":"STS.id","AccessKeySecret":"sec","Expiration":"{}","SecurityToken":"token","LastUpdated":"2023-01-01T00:00:00Z","Code":"Success"}}"#,
+                            expiration
+                                .format(&time::format_description::well_known::Rfc3339)
+                                .unwrap()
+                        ),
+                    )
+                }
+                _ => TransportResponse::new(StatusCode::NOT_FOUND, ""),
+            }
+        });
+        let p = EcsRamRoleCredentials::with_transport(None, t.clone())
+            .with_base_url("http://127.0.0.1:8080/");
+
+        // 首次获取时等待请求完成
+        let c = p.credentials().await.unwrap();
+        assert_eq!("STS.id", c.access_key_id);
+        assert_eq!(Some("token".to_string()), c.security_token);
+        assert!(c.expires_within(REFRESH_AHEAD));
+        assert_eq!(2, t.requests().len());
+
+        // 即将过期，返回旧凭证并在后台刷新
+        let c2 = p.credentials().await.unwrap();
+        assert_eq!(c, c2);
+        for _ in 0..100 {
+            if !p.cached().unwrap().expires_within(REFRESH_AHEAD) {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        let c3 = p.credentials().await.unwrap();
+        assert!(!c3.expires_within(REFRESH_AHEAD));
+        assert_eq!(4, t.requests().len());
+    }
+
+    #[tokio::test]
+    async fn test_chain() {
+        let chain = ChainCredentials::new(vec![
+            Box::new(ProfileCredentials::new().with_path("/nonexistent/config.json")),
+            Box::new(StaticCredentials::new("id", "sec")),
+        ]);
+        assert_eq!(
+            Credentials::new("id", "sec"),
+            chain.credentials().await.unwrap()
+        );
+        assert_eq!(1, chain.last.load(Ordering::Relaxed));
+
+        let chain = ChainCredentials::new(vec![Box::new(
+            ProfileCredentials::new().with_path("/nonexistent/config.json"),
+        )]);
+        assert!(chain.credentials().await.is_err());
+    }
+}
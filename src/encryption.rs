@@ -0,0 +1,256 @@
+//! 消息正文的信封加密，使用 AES-256-GCM
+//! 信封格式：`\0ME` + key id 长度（1 字节）+ key id + 12 字节 nonce + 密文，
+//! 头部作为附加数据参与认证，篡改头部或密文都会得到 [`crate::error::Error::MessageTampered`]
+//!
+//! 设置了 [`KeyProvider`] 时没有信封头部的消息会被拒绝，迁移期间可以用
+//! [`crate::Queue::with_plaintext_allowed`] 临时接收未加密的旧消息
+//! 加密后是二进制数据，队列需要使用 [`crate::queue::BodyEncoding::Base64`]
+use crate::error::Error::{DecryptFailed, EncryptFailed, MessageTampered};
+use crate::error::Result;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+const MAGIC: &[u8] = b"\0ME";
+const NONCE_LEN: usize = 12;
+
+/// AES-256 密钥
+pub type Key = [u8; 32];
+
+/// 提供加密和解密使用的密钥
+/// 加密时使用当前密钥，并把它的 key id 写入信封；解密时按信封中的 key id 查找密钥
+pub trait KeyProvider: Debug + Send + Sync {
+    /// 当前用于加密的密钥及其 key id，key id 不能超过 255 字节
+    fn current_key(&self) -> Result<(String, Key)>;
+    /// 按 key id 查找密钥，找不到时返回 None
+    fn key(&self, key_id: &str) -> Option<Key>;
+}
+
+/// 只有一个密钥
+#[derive(Clone)]
+pub struct StaticKey {
+    key_id: String,
+    key: Key,
+}
+
+impl StaticKey {
+    pub fn new(key_id: &str, key: Key) -> Self {
+        Self {
+            key_id: key_id.to_string(),
+            key,
+        }
+    }
+}
+
+impl Debug for StaticKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StaticKey")
+            .field("key_id", &self.key_id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl KeyProvider for StaticKey {
+    fn current_key(&self) -> Result<(String, Key)> {
+        Ok((self.key_id.clone(), self.key))
+    }
+
+    fn key(&self, key_id: &str) -> Option<Key> {
+        (key_id == self.key_id).then_some(self.key)
+    }
+}
+
+/// 多个密钥，用于密钥轮换：新消息使用当前密钥加密，旧密钥仍可用于解密
+#[derive(Clone)]
+pub struct KeyRing {
+    current: String,
+    keys: HashMap<String, Key>,
+}
+
+impl KeyRing {
+    pub fn new(key_id: &str, key: Key) -> Self {
+        Self {
+            current: key_id.to_string(),
+            keys: HashMap::from([(key_id.to_string(), key)]),
+        }
+    }
+
+    /// 添加只用于解密的旧密钥
+    pub fn with_key(mut self, key_id: &str, key: Key) -> Self {
+        self.keys.insert(key_id.to_string(), key);
+        self
+    }
+
+    /// 切换当前密钥，旧的当前密钥保留用于解密
+    pub fn rotate(mut self, key_id: &str, key: Key) -> Self {
+        self.keys.insert(key_id.to_string(), key);
+        self.current = key_id.to_string();
+        self
+    }
+}
+
+impl Debug for KeyRing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KeyRing")
+            .field("current", &self.current)
+            .field("key_ids", &self.keys.keys().collect::<Vec<_>>())
+            .finish_non_exhaustive()
+    }
+}
+
+impl KeyProvider for KeyRing {
+    fn current_key(&self) -> Result<(String, Key)> {
+        Ok((self.current.clone(), self.keys[&self.current]))
+    }
+
+    fn key(&self, key_id: &str) -> Option<Key> {
+        self.keys.get(key_id).copied()
+    }
+}
+
+/// 加密并封装成信封
+pub(crate) fn seal(provider: &dyn KeyProvider, data: &[u8]) -> Result<Vec<u8>> {
+    let (key_id, key) = provider.current_key()?;
+    let id_len = u8::try_from(key_id.len())
+        .map_err(|_| EncryptFailed(format!("key id {key_id} is longer than 255 bytes")))?;
+    let mut out = Vec::with_capacity(MAGIC.len() + 1 + key_id.len() + NONCE_LEN + data.len() + 16);
+    out.extend_from_slice(MAGIC);
+    out.push(id_len);
+    out.extend_from_slice(key_id.as_bytes());
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = Aes256Gcm::new(&key.into())
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: data,
+                aad: &out,
+            },
+        )
+        .map_err(|e| EncryptFailed(e.to_string()))?;
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+/// 拆开信封并解密
+/// 没有 provider 时不是信封的消息原样返回；有 provider 时只有 allow_plaintext 才接收不是信封的消息
+pub(crate) fn open(
+    provider: Option<&dyn KeyProvider>,
+    data: Vec<u8>,
+    allow_plaintext: bool,
+) -> Result<Vec<u8>> {
+    let sealed = data.starts_with(MAGIC) && data.len() > MAGIC.len();
+    let provider = match provider {
+        Some(p) if sealed => p,
+        Some(_) if allow_plaintext => return Ok(data),
+        Some(_) => return Err(DecryptFailed("message is not encrypted".to_string())),
+        None if sealed => {
+            return Err(DecryptFailed(
+                "message is encrypted but no key provider is set".to_string(),
+            ))
+        }
+        None => return Ok(data),
+    };
+    let id_end = MAGIC.len() + 1 + data[MAGIC.len()] as usize;
+    if data.len() < id_end + NONCE_LEN {
+        return Err(MessageTampered);
+    }
+    let key_id =
+        std::str::from_utf8(&data[MAGIC.len() + 1..id_end]).map_err(|_| MessageTampered)?;
+    let key = provider
+        .key(key_id)
+        .ok_or_else(|| DecryptFailed(format!("unknown key id {key_id}")))?;
+    let (aad, rest) = data.split_at(id_end);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+    Aes256Gcm::new(&key.into())
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad,
+            },
+        )
+        .map_err(|_| MessageTampered)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::Error;
+
+    #[test]
+    fn test_round_trip() {
+        let p = StaticKey::new("k1", [1; 32]);
+        let sealed = seal(&p, b"hello").unwrap();
+        assert!(sealed.starts_with(b"\0ME\x02k1"));
+        assert_eq!(
+            b"hello".to_vec(),
+            open(Some(&p), sealed.clone(), false).unwrap()
+        );
+        assert_eq!(
+            b"plain".to_vec(),
+            open(None, b"plain".to_vec(), false).unwrap()
+        );
+        assert!(matches!(open(None, sealed, false), Err(DecryptFailed(_))));
+    }
+
+    #[test]
+    fn test_plaintext() {
+        let p = StaticKey::new("k1", [1; 32]);
+        assert!(matches!(
+            open(Some(&p), b"plain".to_vec(), false),
+            Err(DecryptFailed(_))
+        ));
+        assert!(matches!(
+            open(Some(&p), vec![], false),
+            Err(DecryptFailed(_))
+        ));
+        assert_eq!(
+            b"plain".to_vec(),
+            open(Some(&p), b"plain".to_vec(), true).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_tampered() {
+        let p = StaticKey::new("k1", [1; 32]);
+        let sealed = seal(&p, b"hello").unwrap();
+
+        let mut m = sealed.clone();
+        *m.last_mut().unwrap() ^= 1;
+        assert!(matches!(
+            open(Some(&p), m, false),
+            Err(Error::MessageTampered)
+        ));
+
+        // 改动 key id 也会导致认证失败
+        let ring = KeyRing::new("k1", [1; 32]).with_key("k2", [1; 32]);
+        let mut m = sealed.clone();
+        m[5] = b'2';
+        assert!(matches!(
+            open(Some(&ring), m, false),
+            Err(Error::MessageTampered)
+        ));
+
+        assert!(matches!(
+            open(Some(&p), sealed[..10].to_vec(), false),
+            Err(Error::MessageTampered)
+        ));
+    }
+
+    #[test]
+    fn test_rotation() {
+        let old = StaticKey::new("k1", [1; 32]);
+        let sealed = seal(&old, b"hello").unwrap();
+
+        let ring = KeyRing::new("k1", [1; 32]).rotate("k2", [2; 32]);
+        assert_eq!("k2", ring.current_key().unwrap().0);
+        assert_eq!(b"hello".to_vec(), open(Some(&ring), sealed, false).unwrap());
+        let sealed = seal(&ring, b"world").unwrap();
+        assert!(matches!(
+            open(Some(&old), sealed, false),
+            Err(DecryptFailed(_))
+        ));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::queue::ErrorResponse;
+use crate::queue::{ErrorResponse, MessageReceiveResponse};
 use thiserror::Error;
 
 /// Aliyun MNS 错误码
@@ -27,6 +27,44 @@ pub enum Error {
     DecodeBodyFailed,
     #[error("get body decode element error")]
     GetBodyDecodeElementError,
+    #[error("decode notification failed: {0}")]
+    DecodeNotificationFailed(String),
+    #[error("verify notification failed: {0}")]
+    VerifyNotificationFailed(String),
+    #[error("deserialize manifest failed: {0}")]
+    DeserializeManifestFailed(String),
+    #[error("invalid argument: {0}")]
+    InvalidArgument(String),
+    #[error("batch response has {1} items, expected {0}")]
+    BatchResponseMismatch(usize, usize),
+    #[error("decode message body failed: {0}")]
+    DecodeMessageBodyFailed(String),
+    #[error("encode payload failed: {0}")]
+    EncodePayloadFailed(String),
+    #[error("decode payload failed: {0}")]
+    DecodePayloadFailed(String),
+    #[error("compress failed: {0}")]
+    CompressFailed(String),
+    #[error("decompress failed: {0}")]
+    DecompressFailed(String),
+    #[error("encrypt failed: {0}")]
+    EncryptFailed(String),
+    #[error("decrypt failed: {0}")]
+    DecryptFailed(String),
+    #[error("message authentication failed, the message may have been tampered with")]
+    MessageTampered,
+    #[error("blob store failed: {0}")]
+    BlobStoreFailed(String),
+    #[error("message body md5 mismatch, expected {0}, got {1}")]
+    BodyDigestMismatch(String, String),
+    #[error("load credentials failed: {0}")]
+    LoadCredentialsFailed(String),
+    /// 收到的消息解码失败，message 是未解码的原始消息，仍然可以用其中的 receipt_handle 删除或修改可见性
+    #[error("decode message {} failed: {source}", message.message_id)]
+    UndecodableMessage {
+        message: Box<MessageReceiveResponse>,
+        source: Box<Error>,
+    },
 
     #[error("unknown error: {0}")]
     MNSUnknown(ErrorResponse),
@@ -176,6 +214,7 @@ impl From<ErrorResponse> for Error {
                 Error::MNSSubscriptionAlreadyExistAndHaveSameAttr(value)
             }
             "QueueNameIsTooLong" => Error::MNSQueueNameIsTooLong(value),
+            "QueueAlreadyExistAndHaveSameAttr" => Error::MNSQueueAlreadyExistAndHaveSameAttr(value),
             "DelaySecondsRangeError" => Error::MNSDelaySecondsRangeError(value),
             "MaxMessageSiZeRangeError" => Error::MNSMaXMessageSiZeRangeError(value),
             "MsgRetentionPeriodRangeError" => Error::MNSMsgRetentionPeriodRangeError(value),
@@ -17,17 +17,39 @@
 //!     }).await.unwrap();
 //! }
 //! ```
+pub mod claim_check;
 pub mod client;
+mod client_builder;
+pub mod codec;
+pub mod compression;
 pub mod consumer;
+pub mod credentials;
 #[cfg(test)]
 pub mod devtool;
+#[cfg(feature = "encryption")]
+pub mod encryption;
 pub mod error;
+#[cfg(feature = "http-endpoint")]
+pub mod http_endpoint;
+pub mod notification;
 pub mod options;
+pub mod provision;
 pub mod queue;
 pub mod queue_manager;
+pub mod retry;
+pub mod subscription;
+pub mod topic;
+pub mod topic_manager;
+pub mod transport;
+pub mod typed;
+mod xml;
 
 /// 消息操作 API，包括消息的发送、接收、删除、修改可见性等操作
 /// <https://help.aliyun.com/document_detail/140735.html>
 pub type Queue = queue::Queue;
 pub type Client = client::Client;
 pub type QueueManager = queue_manager::QueueManager;
+/// 主题消息操作 API，向主题发布消息以及管理订阅
+pub type Topic = topic::Topic;
+/// 主题管理 API，包括主题的创建、删除、属性查询与修改、分页列出
+pub type TopicManager = topic_manager::TopicManager;
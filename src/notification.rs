@@ -0,0 +1,150 @@
+//! 主题推送给订阅者的消息
+//! 订阅的 NotifyContentFormat 为 XML 或 JSON 时，消息正文外面会包一层通知信封
+//! 推送到队列的消息也是如此，可以用 [`MessageReceiveResponse::notification`]
+//! 或 [`crate::consumer::Delivery::notification`] 拆开信封
+use crate::error::Error::DecodeNotificationFailed;
+use crate::error::Result;
+use crate::queue::MessageReceiveResponse;
+use crate::subscription::NotifyContentFormat;
+use serde::{Deserialize, Serialize};
+
+/// XML / JSON 格式的通知信封
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename = "Notification")]
+pub struct Notification {
+    /// 主题所有者的账号 ID
+    #[serde(rename = "TopicOwner")]
+    pub topic_owner: String,
+    #[serde(rename = "TopicName")]
+    pub topic_name: String,
+    /// 订阅者的账号 ID
+    #[serde(rename = "Subscriber")]
+    pub subscriber: String,
+    #[serde(rename = "SubscriptionName")]
+    pub subscription_name: String,
+    #[serde(rename = "MessageId")]
+    pub message_id: String,
+    #[serde(rename = "MessageMD5")]
+    pub message_md5: String,
+    #[serde(rename = "MessageTag", default)]
+    pub message_tag: Option<String>,
+    /// 发布时的消息正文
+    #[serde(rename = "Message")]
+    pub message: String,
+    /// 发布时间，单位毫秒
+    #[serde(rename = "PublishTime")]
+    pub publish_time: i64,
+}
+
+impl Notification {
+    pub fn from_xml(body: &[u8]) -> Result<Self> {
+        serde_xml_rs::from_reader(body).map_err(|e| DecodeNotificationFailed(e.to_string()))
+    }
+
+    pub fn from_json(body: &[u8]) -> Result<Self> {
+        serde_json::from_slice(body).map_err(|e| DecodeNotificationFailed(e.to_string()))
+    }
+}
+
+/// 一条推送，SIMPLIFIED 格式没有信封，只有消息正文
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotificationBody {
+    Notification(Notification),
+    Simplified(String),
+}
+
+impl NotificationBody {
+    /// 按订阅的 NotifyContentFormat 解析推送内容
+    pub fn decode(body: &[u8], format: NotifyContentFormat) -> Result<Self> {
+        match format {
+            NotifyContentFormat::Xml => Notification::from_xml(body).map(Self::Notification),
+            NotifyContentFormat::Json => Notification::from_json(body).map(Self::Notification),
+            NotifyContentFormat::Simplified => String::from_utf8(body.to_vec())
+                .map(Self::Simplified)
+                .map_err(|e| DecodeNotificationFailed(e.to_string())),
+        }
+    }
+
+    /// 发布时的消息正文
+    pub fn message(&self) -> &str {
+        match self {
+            NotificationBody::Notification(n) => n.message.as_str(),
+            NotificationBody::Simplified(m) => m.as_str(),
+        }
+    }
+
+    pub fn notification(&self) -> Option<&Notification> {
+        match self {
+            NotificationBody::Notification(n) => Some(n),
+            NotificationBody::Simplified(_) => None,
+        }
+    }
+}
+
+impl MessageReceiveResponse {
+    /// 主题推送到队列的消息，按订阅的 NotifyContentFormat 拆开通知信封
+    pub fn notification(&self, format: NotifyContentFormat) -> Result<NotificationBody> {
+        NotificationBody::decode(&self.data, format)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Client, Queue};
+
+    #[test]
+    fn test_decode() {
+        let src = r#"<?xml version="1.0" encoding="utf-8"?>
+<Notification xmlns="http://mns.aliyuncs.com/doc/v1/">
+  <TopicOwner>1234</TopicOwner>
+  <TopicName>t1</TopicName>
+  <Subscriber>1234</Subscriber>
+  <SubscriptionName>s1</SubscriptionName>
+  <MessageId>0A0B0C0D-1-1</MessageId>
+  <MessageMD5>7A5A30F65F9D1F3F9E3E8F6C2DF3E8D9</MessageMD5>
+  <MessageTag>important</MessageTag>
+  <Message>hello &lt;mns&gt;</Message>
+  <PublishTime>1449556005000</PublishTime>
+</Notification>"#;
+        let n = NotificationBody::decode(src.as_bytes(), NotifyContentFormat::Xml).unwrap();
+        assert_eq!("hello <mns>", n.message());
+        let n = n.notification().unwrap();
+        assert_eq!("t1", n.topic_name);
+        assert_eq!(Some("important".to_string()), n.message_tag);
+        assert_eq!(1449556005000, n.publish_time);
+
+        let src = r#"{"TopicOwner":"1234","TopicName":"t1","Subscriber":"1234","SubscriptionName":"s1","MessageId":"0A0B0C0D-1-1","MessageMD5":"7A5A30F65F9D1F3F9E3E8F6C2DF3E8D9","Message":"hello","PublishTime":1449556005000}"#;
+        let n = NotificationBody::decode(src.as_bytes(), NotifyContentFormat::Json).unwrap();
+        assert_eq!("hello", n.message());
+        assert_eq!(None, n.notification().unwrap().message_tag);
+
+        let n = NotificationBody::decode(b"hello", NotifyContentFormat::Simplified).unwrap();
+        assert_eq!(NotificationBody::Simplified("hello".to_string()), n);
+
+        assert!(NotificationBody::decode(b"hello", NotifyContentFormat::Json).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_queue_message() {
+        let src = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Message xmlns="http://mns.aliyuncs.com/doc/v1/">
+  <MessageId>5F290C926D472878-2-14D9529A8FA-200000001</MessageId>
+  <ReceiptHandle>1-ODU4OTkzNDU5My0xNDMyNzI3ODI3LTItOA==</ReceiptHandle>
+  <MessageBodyMD5>C5DD56A39F5F7BB8B3337C6D11B6D8C7</MessageBodyMD5>
+  <MessageBody>&lt;?xml version="1.0" encoding="utf-8"?&gt;&lt;Notification xmlns="http://mns.aliyuncs.com/doc/v1/"&gt;&lt;TopicOwner&gt;1234&lt;/TopicOwner&gt;&lt;TopicName&gt;t1&lt;/TopicName&gt;&lt;Subscriber&gt;1234&lt;/Subscriber&gt;&lt;SubscriptionName&gt;s1&lt;/SubscriptionName&gt;&lt;MessageId&gt;0A0B0C0D-1-1&lt;/MessageId&gt;&lt;MessageMD5&gt;5D41402ABC4B2A76B9719D911017C592&lt;/MessageMD5&gt;&lt;Message&gt;hello&lt;/Message&gt;&lt;PublishTime&gt;1449556005000&lt;/PublishTime&gt;&lt;/Notification&gt;</MessageBody>
+  <EnqueueTime>1250700979248</EnqueueTime>
+  <NextVisibleTime>1250700799348</NextVisibleTime>
+  <FirstDequeueTime>1250700779318</FirstDequeueTime>
+  <DequeueCount>1</DequeueCount>
+  <Priority>8</Priority>
+</Message>"#;
+        let m: MessageReceiveResponse = serde_xml_rs::from_str(src).unwrap();
+        let q = Queue::new("q", &Client::new("http://127.0.0.1:1", "id", "sec"));
+        let m = q.decode(m).await.unwrap();
+        let n = m.notification(NotifyContentFormat::Xml).unwrap();
+        assert_eq!("hello", n.message());
+        assert_eq!("t1", n.notification().unwrap().topic_name);
+        assert!(m.notification(NotifyContentFormat::Json).is_err());
+    }
+}
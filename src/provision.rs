@@ -0,0 +1,379 @@
+//! 声明式的队列管理
+//! 在清单中声明队列，与线上队列的属性对比后生成执行计划，再按计划创建、修改或删除队列
+//!
+//! # Example
+//! ```rust,no_run
+//! use mns::provision::QueueManifest;
+//! use mns::{Client, QueueManager};
+//! #[tokio::main]
+//! async fn main() {
+//!     let manifest: QueueManifest = serde_json::from_str(
+//!         r#"{"queues": [{"queue_name": "order-created", "visibility_timeout": 60}]}"#,
+//!     )
+//!     .unwrap();
+//!     let client = Client::new("https://xxx.mns.cn-hangzhou.aliyuncs.com", "your id", "your key");
+//!     let qm = QueueManager::new(&client);
+//!     let plan = qm.reconcile(&manifest).await.unwrap();
+//!     println!("{plan}");
+//!     qm.apply(&plan).await.unwrap();
+//! }
+//! ```
+#[cfg(any(feature = "toml", feature = "yaml"))]
+use crate::error::Error::DeserializeManifestFailed;
+use crate::error::Error::{InvalidArgument, MNSQueueAlreadyExistAndHaveSameAttr};
+use crate::error::{Error, Result};
+use crate::queue_manager::{
+    AttributeChange, CreateQueueRequest, ListQueueRequest, QueueAttribute,
+    SetQueueAttributesRequest,
+};
+use crate::QueueManager;
+use futures::TryStreamExt;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fmt::Display;
+
+/// 队列清单
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QueueManifest {
+    #[serde(default, deserialize_with = "deserialize_queues")]
+    pub queues: Vec<CreateQueueRequest>,
+    /// 设置后，名称以该前缀开头但不在清单中的队列会被删除，不能为空
+    #[serde(default)]
+    pub prune_prefix: Option<String>,
+}
+
+/// 清单中的队列条目，拒绝未知字段，避免拼错的属性被忽略
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct QueueEntry {
+    queue_name: String,
+    delay_seconds: Option<i32>,
+    maximum_message_size: Option<i32>,
+    message_retention_period: Option<i32>,
+    visibility_timeout: Option<i32>,
+    polling_wait_seconds: Option<i32>,
+    logging_enabled: Option<bool>,
+}
+
+impl From<QueueEntry> for CreateQueueRequest {
+    fn from(e: QueueEntry) -> Self {
+        Self {
+            queue_name: e.queue_name,
+            delay_seconds: e.delay_seconds,
+            maximum_message_size: e.maximum_message_size,
+            message_retention_period: e.message_retention_period,
+            visibility_timeout: e.visibility_timeout,
+            polling_wait_seconds: e.polling_wait_seconds,
+            logging_enabled: e.logging_enabled,
+        }
+    }
+}
+
+fn deserialize_queues<'de, D>(d: D) -> core::result::Result<Vec<CreateQueueRequest>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let entries = Vec::<QueueEntry>::deserialize(d)?;
+    Ok(entries.into_iter().map(Into::into).collect())
+}
+
+impl QueueManifest {
+    /// 空的 prune_prefix 会匹配所有队列
+    fn check(&self) -> Result<()> {
+        match &self.prune_prefix {
+            Some(p) if p.trim().is_empty() => Err(InvalidArgument(
+                "prune_prefix must not be empty, it would delete every queue not in the manifest"
+                    .to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    #[cfg(feature = "toml")]
+    pub fn from_toml(s: &str) -> Result<Self> {
+        toml::from_str(s).map_err(|e| DeserializeManifestFailed(e.to_string()))
+    }
+
+    #[cfg(feature = "yaml")]
+    pub fn from_yaml(s: &str) -> Result<Self> {
+        serde_yaml::from_str(s).map_err(|e| DeserializeManifestFailed(e.to_string()))
+    }
+}
+
+impl CreateQueueRequest {
+    /// 只包含与线上队列不同的字段
+    fn set_attributes(&self, changes: &[AttributeChange]) -> SetQueueAttributesRequest {
+        let changed = |field| changes.iter().any(|c| c.field == field);
+        SetQueueAttributesRequest {
+            delay_seconds: self.delay_seconds.filter(|_| changed("DelaySeconds")),
+            maximum_message_size: self
+                .maximum_message_size
+                .filter(|_| changed("MaximumMessageSize")),
+            message_retention_period: self
+                .message_retention_period
+                .filter(|_| changed("MessageRetentionPeriod")),
+            visibility_timeout: self
+                .visibility_timeout
+                .filter(|_| changed("VisibilityTimeout")),
+            polling_wait_seconds: self
+                .polling_wait_seconds
+                .filter(|_| changed("PollingWaitSeconds")),
+            logging_enabled: self.logging_enabled.filter(|_| changed("LoggingEnabled")),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum QueueAction {
+    Create(CreateQueueRequest),
+    Update {
+        queue_name: String,
+        changes: Vec<AttributeChange>,
+        attributes: SetQueueAttributesRequest,
+    },
+    Unchanged(String),
+    Delete(String),
+}
+
+impl QueueAction {
+    fn plan(spec: &CreateQueueRequest, current: Option<&QueueAttribute>) -> Self {
+        let Some(current) = current else {
+            return QueueAction::Create(spec.clone());
+        };
+        let changes = spec.diff(current);
+        if changes.is_empty() {
+            QueueAction::Unchanged(spec.queue_name.clone())
+        } else {
+            QueueAction::Update {
+                queue_name: spec.queue_name.clone(),
+                attributes: spec.set_attributes(&changes),
+                changes,
+            }
+        }
+    }
+}
+
+impl Display for QueueAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueueAction::Create(q) => write!(f, "+ {}", q.queue_name),
+            QueueAction::Update {
+                queue_name,
+                changes,
+                ..
+            } => {
+                write!(f, "~ {queue_name}")?;
+                for c in changes {
+                    write!(f, "\n    {c}")?;
+                }
+                Ok(())
+            }
+            QueueAction::Unchanged(name) => write!(f, "  {name}"),
+            QueueAction::Delete(name) => write!(f, "- {name}"),
+        }
+    }
+}
+
+/// 执行计划
+#[derive(Debug, Clone, Default)]
+pub struct ReconcilePlan {
+    pub actions: Vec<QueueAction>,
+}
+
+impl ReconcilePlan {
+    /// 是否有需要执行的操作
+    pub fn has_changes(&self) -> bool {
+        self.actions
+            .iter()
+            .any(|a| !matches!(a, QueueAction::Unchanged(_)))
+    }
+}
+
+impl Display for ReconcilePlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for a in &self.actions {
+            writeln!(f, "{a}")?;
+        }
+        Ok(())
+    }
+}
+
+impl QueueManager {
+    /// 对比清单与线上队列，生成执行计划，不会修改任何队列
+    pub async fn reconcile(&self, manifest: &QueueManifest) -> Result<ReconcilePlan> {
+        manifest.check()?;
+        let mut actions = vec![];
+        for spec in &manifest.queues {
+            let current = match self.get_queue_attributes(&spec.queue_name).await {
+                Ok(attr) => Some(attr),
+                Err(Error::MNSQueueNotExist(_)) => None,
+                Err(e) => return Err(e),
+            };
+            actions.push(QueueAction::plan(spec, current.as_ref()));
+        }
+
+        if let Some(prefix) = &manifest.prune_prefix {
+            let declared: HashSet<_> = manifest.queues.iter().map(|q| &q.queue_name).collect();
+            let queues: Vec<_> = self
+                .queue_stream(ListQueueRequest {
+                    prefix: Some(prefix.clone()),
+                    ..ListQueueRequest::default()
+                })
+                .try_collect()
+                .await?;
+            for q in queues {
+                let name = q.queue_name().to_string();
+                if !declared.contains(&name) {
+                    actions.push(QueueAction::Delete(name));
+                }
+            }
+        }
+        Ok(ReconcilePlan { actions })
+    }
+
+    /// 按计划依次执行，遇到错误立即返回
+    pub async fn apply(&self, plan: &ReconcilePlan) -> Result<()> {
+        for action in &plan.actions {
+            match action {
+                QueueAction::Create(q) => match self.create_queue(q).await {
+                    Ok(()) | Err(MNSQueueAlreadyExistAndHaveSameAttr(_)) => (),
+                    Err(e) => return Err(e),
+                },
+                QueueAction::Update {
+                    queue_name,
+                    attributes,
+                    ..
+                } => self.set_queue_attributes(queue_name, attributes).await?,
+                QueueAction::Unchanged(_) => (),
+                QueueAction::Delete(name) => self.delete_queue(name).await?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn attr() -> QueueAttribute {
+        QueueAttribute {
+            queue_name: "q1".to_string(),
+            delay_seconds: 0,
+            maximum_message_size: 65536,
+            message_retention_period: 345600,
+            visibility_timeout: 30,
+            polling_wait_seconds: 0,
+            ..QueueAttribute::default()
+        }
+    }
+
+    #[test]
+    fn test_plan() {
+        let spec = CreateQueueRequest {
+            queue_name: "q1".to_string(),
+            visibility_timeout: Some(30),
+            ..CreateQueueRequest::default()
+        };
+        assert!(matches!(
+            QueueAction::plan(&spec, Some(&attr())),
+            QueueAction::Unchanged(_)
+        ));
+        assert!(matches!(
+            QueueAction::plan(&spec, None),
+            QueueAction::Create(_)
+        ));
+
+        let spec = CreateQueueRequest {
+            queue_name: "q1".to_string(),
+            visibility_timeout: Some(60),
+            polling_wait_seconds: Some(0),
+            logging_enabled: Some(true),
+            ..CreateQueueRequest::default()
+        };
+        match QueueAction::plan(&spec, Some(&attr())) {
+            QueueAction::Update {
+                changes,
+                attributes,
+                ..
+            } => {
+                assert_eq!(
+                    vec![
+                        AttributeChange {
+                            field: "VisibilityTimeout",
+                            current: "30".to_string(),
+                            desired: "60".to_string(),
+                        },
+                        AttributeChange {
+                            field: "LoggingEnabled",
+                            current: "false".to_string(),
+                            desired: "true".to_string(),
+                        },
+                    ],
+                    changes
+                );
+                assert_eq!(Some(60), attributes.visibility_timeout);
+                assert_eq!(None, attributes.polling_wait_seconds);
+                assert_eq!(Some(true), attributes.logging_enabled);
+            }
+            a => panic!("unexpected action {a}"),
+        }
+    }
+
+    #[test]
+    fn test_manifest_check() {
+        let m: QueueManifest =
+            serde_json::from_str(r#"{"queues": [{"queue_name": "q1"}], "prune_prefix": " "}"#)
+                .unwrap();
+        assert!(matches!(m.check(), Err(InvalidArgument(_))));
+        let m: QueueManifest = serde_json::from_str(r#"{"prune_prefix": "app-"}"#).unwrap();
+        assert!(m.check().is_ok());
+
+        // 拼错的属性名
+        assert!(serde_json::from_str::<QueueManifest>(
+            r#"{"queues": [{"queue_name": "q1", "visibility_timout": 60}]}"#
+        )
+        .is_err());
+        assert!(serde_json::from_str::<QueueManifest>(r#"{"prune_prefx": "app-"}"#).is_err());
+        // 清单之外的 CreateQueueRequest 仍然忽略未知字段
+        assert!(serde_json::from_str::<CreateQueueRequest>(
+            r#"{"queue_name": "q1", "visibility_timout": 60}"#
+        )
+        .is_ok());
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_toml() {
+        let m = QueueManifest::from_toml(
+            r#"
+prune_prefix = "app-"
+
+[[queues]]
+queue_name = "app-orders"
+visibility_timeout = 60
+logging_enabled = true
+"#,
+        )
+        .unwrap();
+        assert_eq!(Some("app-".to_string()), m.prune_prefix);
+        assert_eq!("app-orders", m.queues[0].queue_name);
+        assert_eq!(Some(60), m.queues[0].visibility_timeout);
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_yaml() {
+        let m = QueueManifest::from_yaml(
+            r#"
+queues:
+  - queue_name: app-orders
+    polling_wait_seconds: 30
+"#,
+        )
+        .unwrap();
+        assert_eq!(None, m.prune_prefix);
+        assert_eq!(Some(30), m.queues[0].polling_wait_seconds);
+    }
+}
@@ -1,13 +1,23 @@
 //! 消息操作 API，包括消息的发送、接收、删除、修改可见性等操作
 //! <https://help.aliyun.com/document_detail/140735.html>
+use crate::claim_check::{parse_reference, ClaimCheckOptions};
 use crate::client::Client;
+use crate::compression::{decompress, CompressionOptions, DEFAULT_MAX_DECOMPRESSED_SIZE};
+#[cfg(feature = "encryption")]
+use crate::encryption::{open, seal, KeyProvider};
 use crate::error::Error::{
-    DeserializeErrorResponseFailed, DeserializeResponseFailed, SerializeMessageFailed,
+    BatchResponseMismatch, BlobStoreFailed, BodyDigestMismatch, DecodeMessageBodyFailed,
+    DeserializeErrorResponseFailed, DeserializeResponseFailed, InvalidArgument, MNSMessageNotExist,
+    SerializeMessageFailed, UndecodableMessage,
 };
 use crate::error::Result;
+use crate::retry::RetryPolicy;
 use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use md5::{Digest, Md5};
 use serde::ser::SerializeStruct;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::fmt::Display;
 
 /// 消息操作 API
@@ -17,6 +27,45 @@ pub struct Queue {
     /// 队列名称
     pub name: String,
     client: Client,
+    encoding: BodyEncoding,
+    compression: Option<CompressionOptions>,
+    max_decompressed_size: usize,
+    #[cfg(feature = "encryption")]
+    encryption: Option<std::sync::Arc<dyn KeyProvider>>,
+    #[cfg(feature = "encryption")]
+    allow_plaintext: bool,
+    claim_check: Option<ClaimCheckOptions>,
+    verify_md5: bool,
+}
+
+/// 消息正文在队列中的编码方式
+/// 官方的 Java / Go / Python SDK 默认使用 Base64，与它们互通时需要设置为 Base64
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BodyEncoding {
+    /// 原样发送，只能发送文本
+    #[default]
+    Raw,
+    /// 发送前 Base64 编码，接收后解码，可以发送二进制数据
+    Base64,
+}
+
+impl BodyEncoding {
+    pub fn encode(&self, data: &[u8]) -> Result<String> {
+        match self {
+            BodyEncoding::Raw => String::from_utf8(data.to_vec())
+                .map_err(|_| InvalidArgument("raw message body must be utf-8".to_string())),
+            BodyEncoding::Base64 => Ok(STANDARD.encode(data)),
+        }
+    }
+
+    pub fn decode(&self, body: &str) -> Result<Vec<u8>> {
+        match self {
+            BodyEncoding::Raw => Ok(body.as_bytes().to_vec()),
+            BodyEncoding::Base64 => STANDARD
+                .decode(body)
+                .map_err(|e| DecodeMessageBodyFailed(e.to_string())),
+        }
+    }
 }
 
 /// <https://help.aliyun.com/document_detail/35134.html#section-exm-22o-0hw>
@@ -60,18 +109,206 @@ pub struct MessageSendResponse {
     pub receipt_handle: Option<String>,
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize)]
 #[serde(rename = "Messages")]
 struct MessageBatchSendRequest {
     #[serde(rename = "Message")]
     pub messages: Vec<MessageSendRequest>,
 }
+// serde-xml-rs 不支持序列化 Vec<struct>，这里逐条写入 Message 节点
+impl Serialize for MessageBatchSendRequest {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut msg = serializer.serialize_struct("Messages", self.messages.len())?;
+        for m in &self.messages {
+            msg.serialize_field("Message", m)?;
+        }
+        msg.end()
+    }
+}
 
+/// 批量发送的响应，部分消息失败时每条消息对应一个 MessageId 或一个 ErrorCode
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename = "Messages")]
 struct MessageBatchSendResponse {
-    #[serde(rename = "Message")]
-    pub messages: Vec<MessageSendResponse>,
+    #[serde(rename = "Message", default)]
+    pub messages: Vec<BatchSendResultItem>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename = "Message")]
+struct BatchSendResultItem {
+    #[serde(rename = "ErrorCode")]
+    pub error_code: Option<String>,
+    #[serde(rename = "ErrorMessage")]
+    pub error_message: Option<String>,
+    #[serde(rename = "MessageId")]
+    pub message_id: Option<String>,
+    #[serde(rename = "MessageBodyMD5")]
+    pub message_body_md5: Option<String>,
+    #[serde(rename = "ReceiptHandle")]
+    pub receipt_handle: Option<String>,
+}
+
+impl From<BatchSendResultItem> for BatchResult<MessageSendResponse> {
+    fn from(value: BatchSendResultItem) -> Self {
+        match (value.error_code, value.message_id) {
+            (None, Some(message_id)) => Ok(MessageSendResponse {
+                message_id,
+                message_body_md5: value.message_body_md5.unwrap_or_default(),
+                receipt_handle: value.receipt_handle,
+            }),
+            (code, _) => Err(BatchItemError {
+                code: code.unwrap_or_default(),
+                message: value.error_message.unwrap_or_default(),
+            }),
+        }
+    }
+}
+
+struct BatchDeleteRequest<'a> {
+    receipt_handles: &'a [&'a str],
+}
+impl Serialize for BatchDeleteRequest<'_> {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut req = serializer.serialize_struct("ReceiptHandles", self.receipt_handles.len())?;
+        for h in self.receipt_handles {
+            req.serialize_field("ReceiptHandle", h)?;
+        }
+        req.end()
+    }
+}
+
+/// 批量删除部分失败时的响应，只包含删除失败的 ReceiptHandle
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename = "Errors")]
+struct BatchDeleteResponse {
+    #[serde(rename = "Error", default)]
+    pub errors: Vec<BatchDeleteErrorItem>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename = "Error")]
+struct BatchDeleteErrorItem {
+    #[serde(rename = "ErrorCode")]
+    pub error_code: String,
+    #[serde(rename = "ErrorMessage")]
+    pub error_message: String,
+    #[serde(rename = "ReceiptHandle")]
+    pub receipt_handle: String,
+}
+
+impl BatchDeleteResponse {
+    /// 按请求中的顺序展开，未出现在响应中的 ReceiptHandle 视为删除成功
+    fn results(self, receipt_handles: &[&str]) -> Vec<BatchResult<()>> {
+        let mut errors = self.errors;
+        receipt_handles
+            .iter()
+            .map(
+                |h| match errors.iter().position(|e| e.receipt_handle == *h) {
+                    Some(i) => {
+                        let e = errors.swap_remove(i);
+                        Err(BatchItemError {
+                            code: e.error_code,
+                            message: e.error_message,
+                        })
+                    }
+                    None => Ok(()),
+                },
+            )
+            .collect()
+    }
+}
+
+/// 单次批量操作最多包含的消息数
+pub const BATCH_MAX_MESSAGES: usize = 16;
+/// 单次批量发送的消息总大小上限，单位字节
+pub const BATCH_MAX_BYTES: usize = 64 * 1024;
+
+/// 长轮询的最长等待时间，单位秒
+pub const MAX_WAIT_SECONDS: u32 = 30;
+
+fn check_num_of_messages(n: u8) -> Result<()> {
+    if n == 0 || n as usize > BATCH_MAX_MESSAGES {
+        return Err(InvalidArgument(format!(
+            "num_of_messages {n} out of range 1~{BATCH_MAX_MESSAGES}"
+        )));
+    }
+    Ok(())
+}
+
+fn not_exist_as_none<T>(r: Result<T>) -> Result<Option<T>> {
+    match r {
+        Ok(m) => Ok(Some(m)),
+        Err(MNSMessageNotExist(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// MessageBodyMD5 的计算方式：消息正文的 MD5，大写十六进制
+pub(crate) fn body_md5(body: &str) -> String {
+    let mut buf = [0u8; 32];
+    base16ct::upper::encode_str(&Md5::digest(body.as_bytes()), &mut buf)
+        .unwrap()
+        .to_string()
+}
+
+/// 批量操作中单条消息的错误
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchItemError {
+    pub code: String,
+    pub message: String,
+}
+
+impl Display for BatchItemError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "code: {}, message: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for BatchItemError {}
+
+impl From<ErrorResponse> for BatchItemError {
+    fn from(value: ErrorResponse) -> Self {
+        Self {
+            code: value.code,
+            message: value.message,
+        }
+    }
+}
+
+/// 整批请求失败（例如网络错误）时，该批中每条消息的错误码，此时无法确定消息是否已入队
+pub const BATCH_REQUEST_FAILED: &str = "BatchRequestFailed";
+
+/// 批量操作中单条消息的结果，与请求中的消息一一对应
+pub type BatchResult<T> = core::result::Result<T, BatchItemError>;
+
+/// 按条数和大小上限将消息切分成多批
+fn split_batches(ms: &[MessageSendRequest]) -> Result<Vec<&[MessageSendRequest]>> {
+    let mut batches = vec![];
+    let (mut start, mut size) = (0, 0);
+    for (i, m) in ms.iter().enumerate() {
+        let len = m.message_body.len();
+        if len > BATCH_MAX_BYTES {
+            return Err(InvalidArgument(format!(
+                "message {i} is {len} bytes, larger than {BATCH_MAX_BYTES}"
+            )));
+        }
+        if i - start == BATCH_MAX_MESSAGES || size + len > BATCH_MAX_BYTES {
+            batches.push(&ms[start..i]);
+            (start, size) = (i, 0);
+        }
+        size += len;
+    }
+    if start < ms.len() {
+        batches.push(&ms[start..]);
+    }
+    Ok(batches)
 }
 
 /// <https://help.aliyun.com/document_detail/35134.html#section-obk-m2u-mzv>
@@ -80,7 +317,8 @@ struct MessageBatchSendResponse {
 pub struct MessageReceiveResponse {
     #[serde(rename = "MessageId")]
     pub message_id: String,
-    #[serde(rename = "ReceiptHandle")]
+    /// 查看消息时没有该字段
+    #[serde(rename = "ReceiptHandle", default)]
     pub receipt_handle: String,
     #[serde(rename = "MessageBodyMD5")]
     pub message_body_md5: String,
@@ -88,7 +326,8 @@ pub struct MessageReceiveResponse {
     pub message_body: String,
     #[serde(rename = "EnqueueTime")]
     pub enqueue_time: i64,
-    #[serde(rename = "NextVisibleTime")]
+    /// 查看消息时没有该字段
+    #[serde(rename = "NextVisibleTime", default)]
     pub next_visible_time: i64,
     #[serde(rename = "FirstDequeueTime")]
     pub first_dequeue_time: i64,
@@ -96,12 +335,16 @@ pub struct MessageReceiveResponse {
     pub dequeue_count: i64,
     #[serde(rename = "Priority")]
     pub priority: i64,
+    /// 按队列的设置解码后的消息正文，message_body 保留收到的原文
+    /// 是文本时可以用 [`MessageReceiveResponse::text`] 读取
+    #[serde(skip)]
+    pub data: Vec<u8>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename = "Messages")]
 pub struct MessageBatchReceiveResponse {
-    #[serde(rename = "Message")]
+    #[serde(rename = "Message", default)]
     pub messages: Vec<MessageReceiveResponse>,
 }
 
@@ -143,7 +386,12 @@ impl Display for ErrorResponse {
 #[async_trait]
 pub trait QueueOperation {
     async fn send_message(&self, m: &MessageSendRequest) -> Result<MessageSendResponse>;
+    /// 消息正文解码失败时返回 [`crate::error::Error::UndecodableMessage`]，其中带有原始消息
     async fn receive_message(&self, wait_seconds: Option<i32>) -> Result<MessageReceiveResponse>;
+    async fn try_receive_message(
+        &self,
+        wait_seconds: Option<i32>,
+    ) -> Result<Option<MessageReceiveResponse>>;
     async fn delete_message(&self, receipt_handle: &str) -> Result<()>;
     async fn change_message_visibility(
         &self,
@@ -151,17 +399,34 @@ pub trait QueueOperation {
         visibility_timeout: i32,
     ) -> Result<MessageVisibilityChangeResponse>;
     async fn peek_message(&self) -> Result<MessageReceiveResponse>;
+    async fn try_peek_message(&self) -> Result<Option<MessageReceiveResponse>>;
 
     async fn batch_send_messages(
         &self,
-        ms: &Vec<MessageSendRequest>,
-    ) -> Result<Vec<MessageSendResponse>>;
+        ms: &[MessageSendRequest],
+    ) -> Result<Vec<BatchResult<MessageSendResponse>>>;
 
+    /// 每条消息单独解码，解码失败的消息以 [`crate::error::Error::UndecodableMessage`] 返回
     async fn batch_receive_message(
         &self,
-        num_of_messages: i32,
+        num_of_messages: u8,
         wait_seconds: Option<u32>,
-    ) -> Result<Vec<MessageReceiveResponse>>;
+    ) -> Result<Vec<Result<MessageReceiveResponse>>>;
+
+    async fn batch_delete_messages(&self, receipt_handles: &[&str])
+        -> Result<Vec<BatchResult<()>>>;
+
+    async fn batch_peek_message(
+        &self,
+        num_of_messages: u8,
+    ) -> Result<Vec<Result<MessageReceiveResponse>>>;
+}
+
+impl MessageReceiveResponse {
+    /// 解码后的消息正文，不是合法的 UTF-8 时返回 None
+    pub fn text(&self) -> Option<&str> {
+        std::str::from_utf8(&self.data).ok()
+    }
 }
 
 impl Queue {
@@ -169,36 +434,457 @@ impl Queue {
         Self {
             name: name.to_string(),
             client: c.clone(),
+            encoding: BodyEncoding::default(),
+            compression: None,
+            max_decompressed_size: DEFAULT_MAX_DECOMPRESSED_SIZE,
+            #[cfg(feature = "encryption")]
+            encryption: None,
+            #[cfg(feature = "encryption")]
+            allow_plaintext: false,
+            claim_check: None,
+            verify_md5: false,
         }
     }
-}
 
-#[async_trait]
-impl QueueOperation for Queue {
-    /// 调用SendMessage接口发送消息到指定的队列
-    /// <https://help.aliyun.com/document_detail/35134.html>
-    async fn send_message(&self, m: &MessageSendRequest) -> Result<MessageSendResponse> {
+    /// 设置消息正文的编码方式，默认为 Raw
+    pub fn with_encoding(mut self, encoding: BodyEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    pub fn encoding(&self) -> BodyEncoding {
+        self.encoding
+    }
+
+    /// 发送时压缩超过阈值的消息，压缩后是二进制数据，需要先设置 Base64 编码，否则返回 InvalidArgument
+    /// 接收时无论是否设置都会按头部解压，未压缩的消息不受影响
+    pub fn with_compression(mut self, compression: CompressionOptions) -> Result<Self> {
+        self.check_binary_encoding("compression")?;
+        self.compression = Some(compression);
+        Ok(self)
+    }
+
+    /// 解压后正文的上限，默认 [`DEFAULT_MAX_DECOMPRESSED_SIZE`]，超过时解码失败
+    pub fn with_max_decompressed_size(mut self, limit: usize) -> Self {
+        self.max_decompressed_size = limit;
+        self
+    }
+
+    /// 发送时加密消息正文，加密后是二进制数据，需要先设置 Base64 编码，否则返回 InvalidArgument
+    /// 接收时按信封中的 key id 解密，未加密的消息返回 DecryptFailed，见 [`Queue::with_plaintext_allowed`]
+    #[cfg(feature = "encryption")]
+    pub fn with_encryption<P: KeyProvider + 'static>(mut self, provider: P) -> Result<Self> {
+        self.check_binary_encoding("encryption")?;
+        self.encryption = Some(std::sync::Arc::new(provider));
+        Ok(self)
+    }
+
+    /// 设置加密后仍然接收未加密的消息，只用于开启加密前已经在队列中的旧消息，迁移完成后应关闭
+    #[cfg(feature = "encryption")]
+    pub fn with_plaintext_allowed(mut self, allow: bool) -> Self {
+        self.allow_plaintext = allow;
+        self
+    }
+
+    fn check_binary_encoding(&self, feature: &str) -> Result<()> {
+        if self.encoding == BodyEncoding::Raw {
+            return Err(InvalidArgument(format!(
+                "{feature} produces binary bodies, set BodyEncoding::Base64 first"
+            )));
+        }
+        Ok(())
+    }
+
+    /// 校验发送响应和收到的消息中的 MessageBodyMD5，不一致时返回 [`BodyDigestMismatch`]
+    pub fn with_md5_verification(mut self, verify_md5: bool) -> Self {
+        self.verify_md5 = verify_md5;
+        self
+    }
+
+    fn check_md5(&self, body: &str, md5: &str) -> Result<()> {
+        if !self.verify_md5 {
+            return Ok(());
+        }
+        let actual = body_md5(body);
+        if actual.eq_ignore_ascii_case(md5) {
+            Ok(())
+        } else {
+            Err(BodyDigestMismatch(md5.to_string(), actual))
+        }
+    }
+
+    /// 发送时把超过阈值的消息正文写入 BlobStore，队列中只保存引用
+    /// 接收时按引用取回正文，Consumer 在调用 [`crate::consumer::Delivery::payload`] 时才读取
+    pub fn with_claim_check(mut self, claim_check: ClaimCheckOptions) -> Self {
+        self.claim_check = Some(claim_check);
+        self
+    }
+
+    /// 使用指定的重试策略，例如 `queue.clone().with_retry_policy(RetryPolicy::none()).send_message(..)`
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.client = self.client.with_retry_policy(retry);
+        self
+    }
+
+    /// 发送二进制消息，Raw 编码时只能发送合法的 UTF-8
+    pub async fn send_bytes(&self, data: &[u8]) -> Result<MessageSendResponse> {
+        let (body, blob) = self.encode_body(data).await?;
+        let m = MessageSendRequest {
+            message_body: body,
+            ..MessageSendRequest::default()
+        };
+        self.send(&m, blob.as_deref()).await
+    }
+
+    /// 依次压缩、加密、写入 BlobStore、编码
+    /// 正文写入了 BlobStore 时同时返回 blob key，发送失败时用于删除
+    pub(crate) async fn encode_body(&self, data: &[u8]) -> Result<(String, Option<String>)> {
+        let data = match &self.compression {
+            Some(c) => Cow::Owned(c.compress(data)?),
+            None => Cow::Borrowed(data),
+        };
+        #[cfg(feature = "encryption")]
+        let data = match &self.encryption {
+            Some(p) => Cow::Owned(seal(p.as_ref(), &data)?),
+            None => data,
+        };
+        match &self.claim_check {
+            Some(c) if data.len() > c.threshold => {
+                let key = c.store.put(&data).await?;
+                Ok((self.encoding.encode(&c.reference(&key))?, Some(key)))
+            }
+            _ => Ok((self.encoding.encode(&data)?, None)),
+        }
+    }
+
+    /// 依次解密、解压
+    fn unseal(&self, data: Vec<u8>) -> Result<Vec<u8>> {
+        #[cfg(feature = "encryption")]
+        let data = open(self.encryption.as_deref(), data, self.allow_plaintext)?;
+        decompress(data, self.max_decompressed_size)
+    }
+
+    /// 从 BlobStore 取回正文
+    pub(crate) async fn load_blob(&self, key: &str) -> Result<Vec<u8>> {
+        let c = self.claim_check.as_ref().ok_or_else(|| {
+            BlobStoreFailed(format!(
+                "message refers to blob {key} but no blob store is set"
+            ))
+        })?;
+        self.unseal(c.store.get(key).await?)
+    }
+
+    /// ack 后按设置删除 blob，key 来自已经校验过签名的引用
+    pub(crate) async fn delete_blob(&self, key: &str) -> Result<()> {
+        match &self.claim_check {
+            Some(c) if c.deletes_on_ack() => c.store.delete(key).await,
+            _ => Ok(()),
+        }
+    }
+
+    /// 删除发送失败的消息已经写入的 blob，删除失败只记录日志
+    async fn discard_blobs<'a>(&self, keys: impl IntoIterator<Item = &'a str>) {
+        let Some(c) = &self.claim_check else {
+            return;
+        };
+        for key in keys {
+            if let Err(e) = c.store.delete(key).await {
+                tracing::warn!("delete blob {key} of unsent message failed, {e}");
+            }
+        }
+    }
+
+    async fn encode(&self, m: &MessageSendRequest) -> Result<(MessageSendRequest, Option<String>)> {
+        let (body, blob) = self.encode_body(m.message_body.as_bytes()).await?;
+        let m = MessageSendRequest {
+            message_body: body,
+            ..m.clone()
+        };
+        Ok((m, blob))
+    }
+
+    /// 按队列的设置解码消息正文，结果写入 data
+    /// 正文是 claim check 引用时不读取 BlobStore，data 为空，同时返回 blob key
+    pub(crate) fn decode_lazy(
+        &self,
+        mut m: MessageReceiveResponse,
+    ) -> Result<(MessageReceiveResponse, Option<String>)> {
+        self.check_md5(&m.message_body, &m.message_body_md5)?;
+        let data = self.encoding.decode(&m.message_body)?;
+        // 没有设置 claim check 的队列不解析引用，正文原样解码
+        if let Some(c) = &self.claim_check {
+            if let Some(r) = parse_reference(&data) {
+                let key = c.verify(&r)?.to_string();
+                return Ok((m, Some(key)));
+            }
+        }
+        m.data = self.unseal(data)?;
+        Ok((m, None))
+    }
+
+    /// 按队列的设置解码消息正文，结果写入 data
+    pub(crate) async fn decode(&self, m: MessageReceiveResponse) -> Result<MessageReceiveResponse> {
+        let (mut m, key) = self.decode_lazy(m)?;
+        if let Some(key) = key {
+            m.data = self.load_blob(&key).await?;
+        }
+        Ok(m)
+    }
+
+    /// 与 decode 相同，失败时返回 [`UndecodableMessage`]，其中带有原始消息
+    async fn decode_received(&self, m: MessageReceiveResponse) -> Result<MessageReceiveResponse> {
+        let raw = m.clone();
+        self.decode(m).await.map_err(|e| UndecodableMessage {
+            message: Box::new(raw),
+            source: Box::new(e),
+        })
+    }
+
+    /// 逐条解码，某条消息解码失败不影响同批的其他消息
+    async fn decode_all(
+        &self,
+        ms: Vec<MessageReceiveResponse>,
+    ) -> Vec<Result<MessageReceiveResponse>> {
+        let mut out = Vec::with_capacity(ms.len());
+        for m in ms {
+            out.push(self.decode_received(m).await);
+        }
+        out
+    }
+
+    /// blob 是 m 引用的 blob key，服务端拒绝消息时删除
+    /// 网络错误时无法确定消息是否已经入队，blob 保留
+    async fn send(
+        &self,
+        m: &MessageSendRequest,
+        blob: Option<&str>,
+    ) -> Result<MessageSendResponse> {
         let (status_code, v) = self
             .client
             .request(
                 &format!("/queues/{}/messages", self.name),
                 "POST",
                 "application/xml",
-                &serde_xml_rs::to_string(m).unwrap(),
-                Some(5),
+                &serde_xml_rs::to_string(m).map_err(SerializeMessageFailed)?,
+                None,
             )
             .await?;
         if status_code.is_success() {
             let res: MessageSendResponse =
                 serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeResponseFailed)?;
+            self.check_md5(&m.message_body, &res.message_body_md5)?;
             Ok(res)
         } else {
             let res: ErrorResponse =
                 serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeErrorResponseFailed)?;
+            self.discard_blobs(blob).await;
             Err(res.into())
         }
     }
 
+    /// 逐条编码 bodies 后替换 ms 中对应的正文，再批量发送
+    /// 某条消息编码失败时删除之前已经写入的 blob
+    pub(crate) async fn batch_send_bodies(
+        &self,
+        ms: &[MessageSendRequest],
+        bodies: &[&[u8]],
+    ) -> Result<Vec<BatchResult<MessageSendResponse>>> {
+        let mut encoded = Vec::with_capacity(ms.len());
+        let mut blobs = Vec::with_capacity(ms.len());
+        for (m, body) in ms.iter().zip(bodies) {
+            match self.encode_body(body).await {
+                Ok((body, blob)) => {
+                    encoded.push(MessageSendRequest {
+                        message_body: body,
+                        ..m.clone()
+                    });
+                    blobs.push(blob);
+                }
+                Err(e) => {
+                    self.discard_blobs(blobs.iter().flatten().map(String::as_str))
+                        .await;
+                    return Err(e);
+                }
+            }
+        }
+        self.batch_send_encoded(&encoded, &blobs).await
+    }
+
+    /// ms 中的消息正文已经按 BodyEncoding 编码，blobs 是与 ms 一一对应的 blob key
+    /// 被服务端拒绝的消息会删除其 blob，整批网络错误或 MD5 不一致时消息可能已经入队，blob 保留
+    async fn batch_send_encoded(
+        &self,
+        ms: &[MessageSendRequest],
+        blobs: &[Option<String>],
+    ) -> Result<Vec<BatchResult<MessageSendResponse>>> {
+        let batches = match split_batches(ms) {
+            Ok(batches) => batches,
+            Err(e) => {
+                self.discard_blobs(blobs.iter().flatten().map(String::as_str))
+                    .await;
+                return Err(e);
+            }
+        };
+        let mut results = Vec::with_capacity(ms.len());
+        for batch in batches {
+            // 一批请求失败时只把这一批标记为失败，已发送的批次结果保留，调用方据此只重发失败的消息
+            let res = match self.batch_send(batch).await {
+                Ok(res) => res,
+                Err(e) => vec![
+                    Err(BatchItemError {
+                        code: BATCH_REQUEST_FAILED.to_string(),
+                        message: e.to_string(),
+                    });
+                    batch.len()
+                ],
+            };
+            results.extend(batch.iter().zip(res).map(|(m, r)| {
+                r.and_then(
+                    |r| match self.check_md5(&m.message_body, &r.message_body_md5) {
+                        Ok(()) => Ok(r),
+                        Err(e) => Err(BatchItemError {
+                            code: "BodyDigestMismatch".to_string(),
+                            message: e.to_string(),
+                        }),
+                    },
+                )
+            }));
+        }
+        let rejected: Vec<&str> = results
+            .iter()
+            .zip(blobs)
+            .filter_map(|(r, blob)| match r {
+                Err(e) if e.code != BATCH_REQUEST_FAILED && e.code != "BodyDigestMismatch" => {
+                    blob.as_deref()
+                }
+                _ => None,
+            })
+            .collect();
+        self.discard_blobs(rejected).await;
+        Ok(results)
+    }
+
+    async fn batch_send(
+        &self,
+        ms: &[MessageSendRequest],
+    ) -> Result<Vec<BatchResult<MessageSendResponse>>> {
+        let req = MessageBatchSendRequest {
+            messages: ms.to_vec(),
+        };
+        let (status_code, v) = self
+            .client
+            .request(
+                &format!("/queues/{}/messages", self.name),
+                "POST",
+                "application/xml",
+                &serde_xml_rs::to_string(&req).map_err(SerializeMessageFailed)?,
+                None,
+            )
+            .await?;
+        if !status_code.is_success() {
+            if let Ok(res) = serde_xml_rs::from_reader::<_, ErrorResponse>(v.as_slice()) {
+                let e = BatchItemError::from(res);
+                return Ok(vec![Err(e); ms.len()]);
+            }
+        }
+        let res: MessageBatchSendResponse =
+            serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeResponseFailed)?;
+        if res.messages.len() != ms.len() {
+            return Err(BatchResponseMismatch(ms.len(), res.messages.len()));
+        }
+        Ok(res.messages.into_iter().map(Into::into).collect())
+    }
+
+    /// 批量消费，不解码消息正文
+    pub(crate) async fn batch_receive_raw(
+        &self,
+        num_of_messages: u8,
+        wait_seconds: Option<u32>,
+    ) -> Result<Vec<MessageReceiveResponse>> {
+        check_num_of_messages(num_of_messages)?;
+        if let Some(w) = wait_seconds.filter(|w| *w > MAX_WAIT_SECONDS) {
+            return Err(InvalidArgument(format!(
+                "wait_seconds {w} out of range 0~{MAX_WAIT_SECONDS}"
+            )));
+        }
+        let resource = wait_seconds.map_or_else(
+            || {
+                format!(
+                    "/queues/{}/messages?numOfMessages={}",
+                    self.name, num_of_messages
+                )
+            },
+            |w| {
+                format!(
+                    "/queues/{}/messages?numOfMessages={}&waitseconds={}",
+                    self.name, num_of_messages, w
+                )
+            },
+        );
+        self.batch_get(&resource, wait_seconds.map(|t| t as i32 + 1))
+            .await
+    }
+
+    /// 批量消费和批量查看共用，队列为空时返回空数组
+    async fn batch_get(
+        &self,
+        resource: &str,
+        timeout_sec: Option<i32>,
+    ) -> Result<Vec<MessageReceiveResponse>> {
+        let (status_code, v) = self
+            .client
+            .request(resource, "GET", "application/xml", "", timeout_sec)
+            .await?;
+        if status_code.is_success() {
+            let res: MessageBatchReceiveResponse =
+                serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeResponseFailed)?;
+            Ok(res.messages)
+        } else {
+            let res: ErrorResponse =
+                serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeErrorResponseFailed)?;
+            match res.into() {
+                MNSMessageNotExist(_) => Ok(vec![]),
+                e => Err(e),
+            }
+        }
+    }
+
+    async fn batch_delete(&self, receipt_handles: &[&str]) -> Result<Vec<BatchResult<()>>> {
+        let req = BatchDeleteRequest { receipt_handles };
+        let (status_code, v) = self
+            .client
+            .request(
+                &format!("/queues/{}/messages", self.name),
+                "DELETE",
+                "application/xml",
+                &serde_xml_rs::to_string(&req).map_err(SerializeMessageFailed)?,
+                None,
+            )
+            .await?;
+        if status_code.is_success() {
+            return Ok(vec![Ok(()); receipt_handles.len()]);
+        }
+        if let Ok(res) = serde_xml_rs::from_reader::<_, BatchDeleteResponse>(v.as_slice()) {
+            if !res.errors.is_empty() {
+                return Ok(res.results(receipt_handles));
+            }
+        }
+        let res: ErrorResponse =
+            serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeErrorResponseFailed)?;
+        let e = BatchItemError::from(res);
+        Ok(vec![Err(e); receipt_handles.len()])
+    }
+}
+
+#[async_trait]
+impl QueueOperation for Queue {
+    /// 调用SendMessage接口发送消息到指定的队列
+    /// <https://help.aliyun.com/document_detail/35134.html>
+    async fn send_message(&self, m: &MessageSendRequest) -> Result<MessageSendResponse> {
+        let (m, blob) = self.encode(m).await?;
+        self.send(&m, blob.as_deref()).await
+    }
+
     /// 调用ReceiveMessage接口消费队列中的消息
     /// <https://help.aliyun.com/document_detail/35136.html>
     async fn receive_message(&self, wait_seconds: Option<i32>) -> Result<MessageReceiveResponse> {
@@ -219,7 +905,7 @@ impl QueueOperation for Queue {
         if status_code.is_success() {
             let res: MessageReceiveResponse =
                 serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeResponseFailed)?;
-            Ok(res)
+            self.decode_received(res).await
         } else {
             let res: ErrorResponse =
                 serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeErrorResponseFailed)?;
@@ -227,6 +913,15 @@ impl QueueOperation for Queue {
         }
     }
 
+    /// 与 receive_message 相同，队列中没有消息时返回 None 而不是 MNSMessageNotExist
+    /// 批量消费 [`QueueOperation::batch_receive_message`] 在没有消息时返回空数组
+    async fn try_receive_message(
+        &self,
+        wait_seconds: Option<i32>,
+    ) -> Result<Option<MessageReceiveResponse>> {
+        not_exist_as_none(self.receive_message(wait_seconds).await)
+    }
+
     /// 调用DeleteMessage接口删除已经被消费过的消息
     /// <https://help.aliyun.com/document_detail/35138.html>
     async fn delete_message(&self, receipt_handle: &str) -> Result<()> {
@@ -240,7 +935,7 @@ impl QueueOperation for Queue {
                 "DELETE",
                 "application/xml",
                 "",
-                Some(5),
+                None,
             )
             .await?;
         if status_code.is_success() {
@@ -268,7 +963,7 @@ impl QueueOperation for Queue {
                 "PUT",
                 "application/xml",
                 "",
-                Some(5),
+                None,
             )
             .await?;
         if status_code.is_success() {
@@ -291,13 +986,13 @@ impl QueueOperation for Queue {
                 "GET",
                 "application/xml",
                 "",
-                Some(5),
+                None,
             )
             .await?;
         if status_code.is_success() {
             let res: MessageReceiveResponse =
                 serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeResponseFailed)?;
-            Ok(res)
+            self.decode_received(res).await
         } else {
             let res: ErrorResponse =
                 serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeErrorResponseFailed)?;
@@ -305,84 +1000,93 @@ impl QueueOperation for Queue {
         }
     }
 
-    /// 暂时不要使用
-    /// 消息批量发送的时候，部分消息失败的异常没有处理
-    /// TODO
+    /// 与 peek_message 相同，队列中没有消息时返回 None 而不是 MNSMessageNotExist
+    async fn try_peek_message(&self) -> Result<Option<MessageReceiveResponse>> {
+        not_exist_as_none(self.peek_message().await)
+    }
+
+    /// 调用BatchSendMessage接口批量发送消息
+    /// 按队列的 BodyEncoding 编码后，超过 16 条或 64KB 时自动分成多批发送，返回的结果与 ms 一一对应
+    /// 某一批整体失败（例如队列不存在）时，该批中的每条消息都会得到同样的错误
     async fn batch_send_messages(
         &self,
-        ms: &Vec<MessageSendRequest>,
-    ) -> Result<Vec<MessageSendResponse>> {
-        let (status_code, v) = self
-            .client
-            .request(
-                &format!("/queues/{}/messages", self.name),
-                "POST",
-                "application/xml",
-                &serde_xml_rs::to_string(&ms).map_err(SerializeMessageFailed)?,
-                Some(5),
-            )
-            .await?;
-        if status_code.is_success() {
-            let res: MessageBatchSendResponse =
-                serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeResponseFailed)?;
-            Ok(res.messages)
-        } else {
-            let res: ErrorResponse =
-                serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeErrorResponseFailed)?;
-            Err(res.into())
-        }
+        ms: &[MessageSendRequest],
+    ) -> Result<Vec<BatchResult<MessageSendResponse>>> {
+        let bodies: Vec<&[u8]> = ms.iter().map(|m| m.message_body.as_bytes()).collect();
+        self.batch_send_bodies(ms, &bodies).await
     }
 
-    /// 暂时不要使用
-    /// 批量处理时，部分消息失败的异常没有处理
-    /// TODO
+    /// 调用BatchReceiveMessage接口批量消费消息
+    /// num_of_messages 取值范围 1~16，wait_seconds 取值范围 0~30
+    /// 队列中没有消息时返回空数组
     async fn batch_receive_message(
         &self,
-        num_of_messages: i32,
+        num_of_messages: u8,
         wait_seconds: Option<u32>,
-    ) -> Result<Vec<MessageReceiveResponse>> {
-        let resource = wait_seconds.map_or_else(
-            || {
-                format!(
-                    "/queues/{}/messages?numOfMessages={}",
+    ) -> Result<Vec<Result<MessageReceiveResponse>>> {
+        let ms = self
+            .batch_receive_raw(num_of_messages, wait_seconds)
+            .await?;
+        Ok(self.decode_all(ms).await)
+    }
+
+    /// 调用BatchPeekMessage接口批量查看消息，不会改变消息的状态
+    /// num_of_messages 取值范围 1~16，队列中没有消息时返回空数组
+    async fn batch_peek_message(
+        &self,
+        num_of_messages: u8,
+    ) -> Result<Vec<Result<MessageReceiveResponse>>> {
+        check_num_of_messages(num_of_messages)?;
+        let ms = self
+            .batch_get(
+                &format!(
+                    "/queues/{}/messages?peekonly=true&numOfMessages={}",
                     self.name, num_of_messages
-                )
-            },
-            |w| {
-                format!(
-                    "/queues/{}/messages?numOfMessages={}&waitseconds={}",
-                    self.name, num_of_messages, w
-                )
-            },
-        );
-        let (status_code, v) = self
-            .client
-            .request(
-                &resource,
-                "GET",
-                "application/xml",
-                "",
-                wait_seconds.map(|t| t as i32 + 1),
+                ),
+                None,
             )
             .await?;
-        if status_code.is_success() {
-            let res: MessageBatchReceiveResponse =
-                serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeResponseFailed)?;
-            Ok(res.messages)
-        } else {
-            let res: ErrorResponse =
-                serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeErrorResponseFailed)?;
-            Err(res.into())
+        Ok(self.decode_all(ms).await)
+    }
+
+    /// 调用BatchDeleteMessage接口批量删除消息
+    /// 超过 16 条时自动分成多批删除，返回的结果与 receipt_handles 一一对应
+    /// 单条失败（例如 ReceiptHandleError、MessageNotExist）不影响同批中的其他消息
+    async fn batch_delete_messages(
+        &self,
+        receipt_handles: &[&str],
+    ) -> Result<Vec<BatchResult<()>>> {
+        let mut results = Vec::with_capacity(receipt_handles.len());
+        for batch in receipt_handles.chunks(BATCH_MAX_MESSAGES) {
+            results.extend(self.batch_delete(batch).await?);
         }
+        Ok(results)
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+
     use crate::devtool::{get_client, get_queue_name};
     use serde_xml_rs::to_string;
 
+    /// 构造一条收到的消息，正文为 body
+    fn received(body: &str) -> MessageReceiveResponse {
+        MessageReceiveResponse {
+            message_id: "1".to_string(),
+            receipt_handle: "h-1".to_string(),
+            message_body_md5: body_md5(body),
+            message_body: body.to_string(),
+            enqueue_time: 0,
+            next_visible_time: 0,
+            first_dequeue_time: 0,
+            dequeue_count: 1,
+            priority: 8,
+            data: Vec::new(),
+        }
+    }
+
     #[test]
     fn test_serde() {
         let src = r#"<?xml version="1.0" encoding="UTF-8"?><Message><MessageBody>aa</MessageBody><DelaySeconds>1</DelaySeconds><Priority>9</Priority></Message>"#;
@@ -410,6 +1114,497 @@ mod test {
         assert_eq!(src, to_string(&m).unwrap());
     }
 
+    #[test]
+    fn test_batch_send_serde() {
+        let src = r#"<?xml version="1.0" encoding="UTF-8"?><Messages><Message><MessageBody>a</MessageBody></Message><Message><MessageBody>b</MessageBody><DelaySeconds>1</DelaySeconds></Message></Messages>"#;
+        let req = MessageBatchSendRequest {
+            messages: vec![
+                MessageSendRequest {
+                    message_body: "a".to_string(),
+                    ..MessageSendRequest::default()
+                },
+                MessageSendRequest {
+                    message_body: "b".to_string(),
+                    delay_seconds: Some(1),
+                    priority: None,
+                },
+            ],
+        };
+        assert_eq!(src, to_string(&req).unwrap());
+
+        let src = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Messages xmlns="http://mns.aliyuncs.com/doc/v1/">
+  <Message>
+    <ErrorCode>InternalError</ErrorCode>
+    <ErrorMessage>internal error</ErrorMessage>
+  </Message>
+  <Message>
+    <MessageId>5F290C926D472878-2-14D9529A8FA-200000002</MessageId>
+    <MessageBodyMD5>C5DD56A39F5F7BB8B3337C6D11B6D8C7</MessageBodyMD5>
+  </Message>
+</Messages>"#;
+        let res: MessageBatchSendResponse = serde_xml_rs::from_str(src).unwrap();
+        let res: Vec<BatchResult<MessageSendResponse>> =
+            res.messages.into_iter().map(Into::into).collect();
+        assert_eq!(
+            Err(BatchItemError {
+                code: "InternalError".to_string(),
+                message: "internal error".to_string(),
+            }),
+            res[0].clone().map(|_| ())
+        );
+        assert_eq!(
+            "5F290C926D472878-2-14D9529A8FA-200000002",
+            res[1].as_ref().unwrap().message_id
+        );
+    }
+
+    #[tokio::test]
+    async fn test_batch_send_chunk_failure() {
+        use crate::transport::{MemoryTransport, TransportResponse};
+        use http::StatusCode;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let n = AtomicUsize::new(0);
+        let t = MemoryTransport::new(move |_| {
+            if n.fetch_add(1, Ordering::SeqCst) == 0 {
+                let items =
+                    "<Message><MessageId>1</MessageId><MessageBodyMD5>0</MessageBodyMD5></Message>"
+                        .repeat(BATCH_MAX_MESSAGES);
+                TransportResponse::new(StatusCode::CREATED, format!("<Messages>{items}</Messages>"))
+            } else {
+                TransportResponse::new(StatusCode::BAD_GATEWAY, "bad gateway")
+            }
+        });
+        let q = Queue::new(
+            "q1",
+            &Client::with_transport("http://mns.test", "id", "sec", t),
+        );
+        let ms = vec![
+            MessageSendRequest {
+                message_body: "a".to_string(),
+                ..MessageSendRequest::default()
+            };
+            BATCH_MAX_MESSAGES + 2
+        ];
+        let res = q.batch_send_messages(&ms).await.unwrap();
+        assert_eq!(ms.len(), res.len());
+        assert!(res[..BATCH_MAX_MESSAGES].iter().all(|r| r.is_ok()));
+        for r in &res[BATCH_MAX_MESSAGES..] {
+            assert_eq!(BATCH_REQUEST_FAILED, r.as_ref().unwrap_err().code);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_undecodable_message() {
+        use crate::error::Error;
+        use crate::transport::{MemoryTransport, TransportResponse};
+        use http::StatusCode;
+
+        let message = |id: &str, body: &str| {
+            format!(
+                "<Message><MessageId>{id}</MessageId><ReceiptHandle>h-{id}</ReceiptHandle>\
+                 <MessageBodyMD5>0</MessageBodyMD5><MessageBody>{body}</MessageBody>\
+                 <EnqueueTime>0</EnqueueTime><NextVisibleTime>0</NextVisibleTime>\
+                 <FirstDequeueTime>0</FirstDequeueTime><DequeueCount>3</DequeueCount>\
+                 <Priority>8</Priority></Message>"
+            )
+        };
+        let single = message("1", "not base64!");
+        let batch = format!(
+            "<Messages>{}{}</Messages>",
+            message("1", "not base64!"),
+            message("2", "aGVsbG8=")
+        );
+        let t = MemoryTransport::new(move |req| {
+            if req.url.contains("numOfMessages") {
+                TransportResponse::new(StatusCode::OK, batch.clone())
+            } else {
+                TransportResponse::new(StatusCode::OK, single.clone())
+            }
+        });
+        let q = Queue::new(
+            "q1",
+            &Client::with_transport("http://mns.test", "id", "sec", t),
+        )
+        .with_encoding(BodyEncoding::Base64);
+
+        match q.receive_message(Some(1)).await {
+            Err(Error::UndecodableMessage { message, source }) => {
+                assert_eq!("h-1", message.receipt_handle);
+                assert_eq!("not base64!", message.message_body);
+                assert!(matches!(*source, DecodeMessageBodyFailed(_)));
+            }
+            r => panic!("unexpected result {r:?}"),
+        }
+
+        let ms = q.batch_receive_message(2, Some(1)).await.unwrap();
+        assert_eq!(2, ms.len());
+        match &ms[0] {
+            Err(Error::UndecodableMessage { message, .. }) => {
+                assert_eq!("h-1", message.receipt_handle);
+                assert_eq!(3, message.dequeue_count);
+            }
+            r => panic!("unexpected result {r:?}"),
+        }
+        assert_eq!(b"hello".to_vec(), ms[1].as_ref().unwrap().data);
+    }
+
+    #[tokio::test]
+    async fn test_batch_receive_args() {
+        let q = Queue::new("q", &Client::new("http://127.0.0.1:1", "id", "sec"));
+        for (n, w) in [(0, None), (17, None), (1, Some(31))] {
+            match q.batch_receive_message(n, w).await {
+                Err(InvalidArgument(_)) => (),
+                r => panic!("unexpected result {r:?}"),
+            }
+        }
+        for n in [0, 17] {
+            match q.batch_peek_message(n).await {
+                Err(InvalidArgument(_)) => (),
+                r => panic!("unexpected result {r:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_batch_peek_serde() {
+        let src = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Messages xmlns="http://mns.aliyuncs.com/doc/v1/">
+  <Message>
+    <MessageId>5F290C926D472878-2-14D9529A8FA-200000001</MessageId>
+    <MessageBodyMD5>C5DD56A39F5F7BB8B3337C6D11B6D8C7</MessageBodyMD5>
+    <MessageBody>a</MessageBody>
+    <EnqueueTime>1250700979248</EnqueueTime>
+    <FirstDequeueTime>1250700979348</FirstDequeueTime>
+    <DequeueCount>5</DequeueCount>
+    <Priority>8</Priority>
+  </Message>
+  <Message>
+    <MessageId>5F290C926D472878-2-14D9529A8FA-200000002</MessageId>
+    <MessageBodyMD5>C5DD56A39F5F7BB8B3337C6D11B6D8C7</MessageBodyMD5>
+    <MessageBody>b</MessageBody>
+    <EnqueueTime>1250700979248</EnqueueTime>
+    <FirstDequeueTime>1250700979348</FirstDequeueTime>
+    <DequeueCount>1</DequeueCount>
+    <Priority>8</Priority>
+  </Message>
+</Messages>"#;
+        let res: MessageBatchReceiveResponse = serde_xml_rs::from_str(src).unwrap();
+        assert_eq!(2, res.messages.len());
+        assert_eq!("b", res.messages[1].message_body);
+        assert_eq!("", res.messages[1].receipt_handle);
+    }
+
+    #[test]
+    fn test_batch_delete_serde() {
+        let src = r#"<?xml version="1.0" encoding="UTF-8"?><ReceiptHandles><ReceiptHandle>h1</ReceiptHandle><ReceiptHandle>h2</ReceiptHandle></ReceiptHandles>"#;
+        let req = BatchDeleteRequest {
+            receipt_handles: &["h1", "h2"],
+        };
+        assert_eq!(src, to_string(&req).unwrap());
+
+        let src = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Errors xmlns="http://mns.aliyuncs.com/doc/v1/">
+  <Error>
+    <ErrorCode>ReceiptHandleError</ErrorCode>
+    <ErrorMessage>The receipt handle you provide is not valid.</ErrorMessage>
+    <ReceiptHandle>h3</ReceiptHandle>
+  </Error>
+  <Error>
+    <ErrorCode>MessageNotExist</ErrorCode>
+    <ErrorMessage>Message not exist.</ErrorMessage>
+    <ReceiptHandle>h1</ReceiptHandle>
+  </Error>
+</Errors>"#;
+        let res: BatchDeleteResponse = serde_xml_rs::from_str(src).unwrap();
+        let res = res.results(&["h1", "h2", "h3"]);
+        assert_eq!("MessageNotExist", res[0].as_ref().unwrap_err().code);
+        assert_eq!(Ok(()), res[1]);
+        assert_eq!("ReceiptHandleError", res[2].as_ref().unwrap_err().code);
+    }
+
+    #[tokio::test]
+    async fn test_body_encoding() {
+        let q = Queue::new("q", &Client::new("http://127.0.0.1:1", "id", "sec"))
+            .with_encoding(BodyEncoding::Base64);
+        let (m, _) = q
+            .encode(&MessageSendRequest {
+                message_body: "hello".to_string(),
+                priority: Some(1),
+                ..MessageSendRequest::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!("aGVsbG8=", m.message_body);
+        assert_eq!(Some(1), m.priority);
+
+        let src = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Message xmlns="http://mns.aliyuncs.com/doc/v1/">
+  <MessageId>5F290C926D472878-2-14D9529A8FA-200000001</MessageId>
+  <ReceiptHandle>1-ODU4OTkzNDU5My0xNDMyNzI3ODI3LTItOA==</ReceiptHandle>
+  <MessageBodyMD5>C5DD56A39F5F7BB8B3337C6D11B6D8C7</MessageBodyMD5>
+  <MessageBody>aGVsbG8=</MessageBody>
+  <EnqueueTime>1250700979248</EnqueueTime>
+  <NextVisibleTime>1250700799348</NextVisibleTime>
+  <FirstDequeueTime>1250700779318</FirstDequeueTime>
+  <DequeueCount>1</DequeueCount>
+  <Priority>8</Priority>
+</Message>"#;
+        let m: MessageReceiveResponse = serde_xml_rs::from_str(src).unwrap();
+        let m = q.decode(m).await.unwrap();
+        assert_eq!("aGVsbG8=", m.message_body);
+        assert_eq!(b"hello".to_vec(), m.data);
+        assert_eq!(Some("hello"), m.text());
+
+        let m = MessageReceiveResponse {
+            message_body: "/w==".to_string(),
+            ..m
+        };
+        let m = q.decode(m).await.unwrap();
+        assert_eq!("/w==", m.message_body);
+        assert_eq!(vec![0xff], m.data);
+        assert_eq!(None, m.text());
+
+        assert!(BodyEncoding::Raw.encode(&[0xff]).is_err());
+        assert!(BodyEncoding::Base64.decode("not base64!").is_err());
+    }
+
+    #[cfg(feature = "encryption")]
+    #[tokio::test]
+    async fn test_encrypted_body() {
+        use crate::encryption::StaticKey;
+        let c = Client::new("http://127.0.0.1:1", "id", "sec");
+        let q = Queue::new("q", &c)
+            .with_encoding(BodyEncoding::Base64)
+            .with_encryption(StaticKey::new("k1", [7; 32]))
+            .unwrap();
+        let (body, _) = q.encode_body(b"secret").await.unwrap();
+        assert!(!STANDARD.decode(&body).unwrap().ends_with(b"secret"));
+        assert_eq!(
+            b"secret".to_vec(),
+            q.decode(received(&body)).await.unwrap().data
+        );
+
+        let plain = Queue::new("q", &c).with_encoding(BodyEncoding::Base64);
+        assert!(matches!(
+            plain.decode(received(&body)).await,
+            Err(crate::error::Error::DecryptFailed(_))
+        ));
+        assert!(matches!(
+            q.decode(received("b2xk")).await,
+            Err(crate::error::Error::DecryptFailed(_))
+        ));
+        let q = q.with_plaintext_allowed(true);
+        assert_eq!(
+            b"old".to_vec(),
+            q.decode(received("b2xk")).await.unwrap().data
+        );
+        assert!(matches!(
+            Queue::new("q", &c).with_encryption(StaticKey::new("k1", [7; 32])),
+            Err(InvalidArgument(_))
+        ));
+    }
+
+    #[cfg(feature = "gzip")]
+    #[tokio::test]
+    async fn test_compressed_body() {
+        use crate::compression::Compression;
+        let c = Client::new("http://127.0.0.1:1", "id", "sec");
+        let opt = CompressionOptions {
+            algorithm: Compression::Gzip,
+            threshold: 64,
+        };
+        assert!(matches!(
+            Queue::new("q", &c).with_compression(opt),
+            Err(InvalidArgument(_))
+        ));
+
+        let q = Queue::new("q", &c)
+            .with_encoding(BodyEncoding::Base64)
+            .with_compression(opt)
+            .unwrap();
+        let data = "0123456789".repeat(100);
+        let (body, _) = q.encode_body(data.as_bytes()).await.unwrap();
+        assert!(body.len() < data.len());
+        assert_eq!(
+            data.as_bytes(),
+            q.decode(received(&body)).await.unwrap().data
+        );
+
+        let q = q.with_max_decompressed_size(data.len() - 1);
+        assert!(matches!(
+            q.decode(received(&body)).await,
+            Err(crate::error::Error::DecompressFailed(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_claim_check() {
+        use crate::claim_check::MemoryBlobStore;
+        let store = MemoryBlobStore::new();
+        let q = Queue::new("q", &Client::new("http://127.0.0.1:1", "id", "sec"))
+            .with_claim_check(ClaimCheckOptions::new(store.clone(), 8).delete_on_ack(b"secret"));
+        assert_eq!(
+            ("small".to_string(), None),
+            q.encode_body(b"small").await.unwrap()
+        );
+        assert!(store.is_empty());
+
+        let (body, blob) = q.encode_body(b"larger than 8 bytes").await.unwrap();
+        assert!(body.starts_with("mns-claim-check:v2:"));
+        assert_eq!(1, store.len());
+        assert_eq!(
+            b"larger than 8 bytes".to_vec(),
+            q.decode(received(&body)).await.unwrap().data
+        );
+
+        // 没有签名的引用不会被读取
+        let forged = format!("mns-claim-check:v1:{}", blob.as_ref().unwrap());
+        assert!(matches!(
+            q.decode(received(&forged)).await,
+            Err(crate::error::Error::MessageTampered)
+        ));
+
+        q.delete_blob(&blob.unwrap()).await.unwrap();
+        assert!(store.is_empty());
+        assert!(q.decode(received(&body)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reference_without_claim_check() {
+        let q = Queue::new("q", &Client::new("http://127.0.0.1:1", "id", "sec"));
+        let body = "mns-claim-check:v1:not-a-blob";
+        let (m, blob) = q
+            .encode(&MessageSendRequest {
+                message_body: body.to_string(),
+                ..MessageSendRequest::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(None, blob);
+        let m = q.decode(received(&m.message_body)).await.unwrap();
+        assert_eq!(body.as_bytes(), m.data);
+    }
+
+    #[tokio::test]
+    async fn test_claim_check_send_failure() {
+        use crate::claim_check::MemoryBlobStore;
+        use crate::transport::{MemoryTransport, TransportResponse};
+        use http::StatusCode;
+
+        let error = "<Error><Code>QueueNotExist</Code><RequestId>r</RequestId>\
+                     <HostId>h</HostId><Message>queue not exist</Message></Error>";
+        let t = MemoryTransport::new(move |req| {
+            let body = String::from_utf8_lossy(&req.body);
+            if body.contains("<Messages>") {
+                // 第一条被拒绝，第二条成功
+                TransportResponse::new(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "<Messages><Message><ErrorCode>InvalidArgument</ErrorCode>\
+                     <ErrorMessage>bad</ErrorMessage></Message><Message><MessageId>2</MessageId>\
+                     <MessageBodyMD5>0</MessageBodyMD5></Message></Messages>",
+                )
+            } else {
+                TransportResponse::new(StatusCode::NOT_FOUND, error)
+            }
+        });
+        let store = MemoryBlobStore::new();
+        let q = Queue::new(
+            "q1",
+            &Client::with_transport("http://mns.test", "id", "sec", t),
+        )
+        .with_retry_policy(RetryPolicy::none())
+        .with_claim_check(ClaimCheckOptions::new(store.clone(), 8));
+
+        assert!(q.send_bytes(b"larger than 8 bytes").await.is_err());
+        assert!(store.is_empty());
+
+        let ms = vec![
+            MessageSendRequest {
+                message_body: "larger than 8 bytes".to_string(),
+                ..MessageSendRequest::default()
+            };
+            2
+        ];
+        let res = q.batch_send_messages(&ms).await.unwrap();
+        assert!(res[0].is_err());
+        assert!(res[1].is_ok());
+        // 只删除被拒绝的消息的 blob
+        assert_eq!(1, store.len());
+    }
+
+    #[tokio::test]
+    async fn test_md5_verification() {
+        assert_eq!("5D41402ABC4B2A76B9719D911017C592", body_md5("hello"));
+        let q = Queue::new("q", &Client::new("http://127.0.0.1:1", "id", "sec"));
+        let m = MessageReceiveResponse {
+            message_id: "id".to_string(),
+            receipt_handle: "h".to_string(),
+            message_body_md5: "5D41402ABC4B2A76B9719D911017C592".to_string(),
+            message_body: "hellO".to_string(),
+            enqueue_time: 0,
+            next_visible_time: 0,
+            first_dequeue_time: 0,
+            dequeue_count: 1,
+            priority: 8,
+            data: vec![],
+        };
+        assert!(q.decode(m.clone()).await.is_ok());
+
+        let q = q.with_md5_verification(true);
+        match q.decode(m.clone()).await {
+            Err(BodyDigestMismatch(expected, actual)) => {
+                assert_eq!("5D41402ABC4B2A76B9719D911017C592", expected);
+                assert_eq!(body_md5("hellO"), actual);
+            }
+            r => panic!("unexpected result {r:?}"),
+        }
+        let m = MessageReceiveResponse {
+            message_body: "hello".to_string(),
+            message_body_md5: "5d41402abc4b2a76b9719d911017c592".to_string(),
+            ..m
+        };
+        assert_eq!(b"hello".to_vec(), q.decode(m).await.unwrap().data);
+    }
+
+    #[test]
+    fn test_not_exist_as_none() {
+        let e = ErrorResponse {
+            code: "MessageNotExist".to_string(),
+            request_id: "r".to_string(),
+            host_id: "h".to_string(),
+            message: "Message not exist.".to_string(),
+        };
+        assert_eq!(
+            None,
+            not_exist_as_none::<()>(Err(e.clone().into())).unwrap()
+        );
+        assert_eq!(Some(1), not_exist_as_none(Ok(1)).unwrap());
+        let e = ErrorResponse {
+            code: "QueueNotExist".to_string(),
+            ..e
+        };
+        assert!(not_exist_as_none::<()>(Err(e.into())).is_err());
+    }
+
+    #[test]
+    fn test_split_batches() {
+        let m = |len| MessageSendRequest {
+            message_body: "a".repeat(len),
+            ..MessageSendRequest::default()
+        };
+        let lens = |ms: &[MessageSendRequest]| -> Vec<usize> {
+            split_batches(ms).unwrap().iter().map(|b| b.len()).collect()
+        };
+
+        assert!(split_batches(&[]).unwrap().is_empty());
+        assert_eq!(vec![16, 16, 1], lens(&vec![m(1); 33]));
+        assert_eq!(vec![2, 2], lens(&vec![m(30 * 1024); 4]));
+        assert_eq!(vec![1, 1], lens(&[m(BATCH_MAX_BYTES), m(1)]));
+        assert!(split_batches(&[m(1), m(BATCH_MAX_BYTES + 1)]).is_err());
+    }
+
     #[tokio::test]
     async fn test_send_message() {
         let c = get_client();
@@ -2,11 +2,16 @@
 //! https://help.aliyun.com/document_detail/140734.html
 
 use crate::client::Client;
-use crate::error::Error::{DeserializeErrorResponseFailed, DeserializeResponseFailed};
+use crate::error::Error::{self, DeserializeErrorResponseFailed, DeserializeResponseFailed};
 use crate::error::Result;
 use crate::queue::ErrorResponse;
+use crate::xml::{bool_str, deserialize_bool};
+use futures::stream::{self, Stream, TryStreamExt};
+use http::StatusCode;
 use serde::ser::SerializeStruct;
 use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::future::Future;
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename = "Queue")]
@@ -33,6 +38,12 @@ pub struct QueueAttribute {
     pub create_time: i64,
     #[serde(rename = "LastModifyTime")]
     pub last_modify_time: i64,
+    #[serde(
+        rename = "LoggingEnabled",
+        default,
+        deserialize_with = "deserialize_bool"
+    )]
+    pub logging_enabled: bool,
 }
 #[derive(Debug, Clone, Default, Deserialize)]
 pub struct CreateQueueRequest {
@@ -67,16 +78,215 @@ impl Serialize for CreateQueueRequest {
             msg.serialize_field("PollingWaitSeconds", &v)?;
         }
         if let Some(v) = self.logging_enabled {
-            let v = match v {
-                true => "True",
-                false => "False",
-            };
-            msg.serialize_field("LoggingEnabled", &v)?;
+            msg.serialize_field("LoggingEnabled", bool_str(v))?;
         }
         msg.end()
     }
 }
 
+/// 单个属性的差异
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttributeChange {
+    pub field: &'static str,
+    pub current: String,
+    pub desired: String,
+}
+
+impl Display for AttributeChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} -> {}", self.field, self.current, self.desired)
+    }
+}
+
+impl CreateQueueRequest {
+    /// 与线上队列的属性对比，只比较设置了的字段
+    pub fn diff(&self, attr: &QueueAttribute) -> Vec<AttributeChange> {
+        let mut changes = vec![];
+        let mut cmp = |field, desired: Option<String>, current: String| {
+            if let Some(desired) = desired {
+                if desired != current {
+                    changes.push(AttributeChange {
+                        field,
+                        current,
+                        desired,
+                    });
+                }
+            }
+        };
+        cmp(
+            "DelaySeconds",
+            self.delay_seconds.map(|v| v.to_string()),
+            attr.delay_seconds.to_string(),
+        );
+        cmp(
+            "MaximumMessageSize",
+            self.maximum_message_size.map(|v| v.to_string()),
+            attr.maximum_message_size.to_string(),
+        );
+        cmp(
+            "MessageRetentionPeriod",
+            self.message_retention_period.map(|v| v.to_string()),
+            attr.message_retention_period.to_string(),
+        );
+        cmp(
+            "VisibilityTimeout",
+            self.visibility_timeout.map(|v| v.to_string()),
+            attr.visibility_timeout.to_string(),
+        );
+        cmp(
+            "PollingWaitSeconds",
+            self.polling_wait_seconds.map(|v| v.to_string()),
+            attr.polling_wait_seconds.to_string(),
+        );
+        cmp(
+            "LoggingEnabled",
+            self.logging_enabled.map(|v| v.to_string()),
+            attr.logging_enabled.to_string(),
+        );
+        changes
+    }
+}
+
+/// 修改队列属性，未设置的字段保持不变
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SetQueueAttributesRequest {
+    pub delay_seconds: Option<i32>,
+    pub maximum_message_size: Option<i32>,
+    pub message_retention_period: Option<i32>,
+    pub visibility_timeout: Option<i32>,
+    pub polling_wait_seconds: Option<i32>,
+    pub logging_enabled: Option<bool>,
+}
+impl Serialize for SetQueueAttributesRequest {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut msg = serializer.serialize_struct("Queue", 6)?;
+        if let Some(d) = self.delay_seconds {
+            msg.serialize_field("DelaySeconds", &d)?;
+        }
+        if let Some(v) = self.maximum_message_size {
+            msg.serialize_field("MaximumMessageSize", &v)?;
+        }
+        if let Some(v) = self.message_retention_period {
+            msg.serialize_field("MessageRetentionPeriod", &v)?;
+        }
+        if let Some(v) = self.visibility_timeout {
+            msg.serialize_field("VisibilityTimeout", &v)?;
+        }
+        if let Some(v) = self.polling_wait_seconds {
+            msg.serialize_field("PollingWaitSeconds", &v)?;
+        }
+        if let Some(v) = self.logging_enabled {
+            msg.serialize_field("LoggingEnabled", bool_str(v))?;
+        }
+        msg.end()
+    }
+}
+
+/// 分页列出队列的参数
+#[derive(Debug, Clone, Default)]
+pub struct ListQueueRequest {
+    /// 按照队列名称前缀查询
+    pub prefix: Option<String>,
+    /// 上一次请求返回的 NextMarker
+    pub marker: Option<String>,
+    /// 单次返回的最大个数，取值范围 1~1000，默认 1000
+    pub ret_number: Option<u32>,
+}
+
+impl ListQueueRequest {
+    fn headers(&self, with_meta: bool) -> Vec<(&str, String)> {
+        let mut headers = vec![];
+        if let Some(v) = &self.prefix {
+            headers.push(("x-mns-prefix", v.clone()));
+        }
+        if let Some(v) = &self.marker {
+            headers.push(("x-mns-marker", v.clone()));
+        }
+        if let Some(v) = self.ret_number {
+            headers.push(("x-mns-ret-number", v.to_string()));
+        }
+        if with_meta {
+            headers.push(("x-mns-with-meta", "true".to_string()));
+        }
+        headers
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename = "Queue")]
+pub struct QueueUrl {
+    #[serde(rename = "QueueURL")]
+    pub queue_url: String,
+}
+
+impl QueueUrl {
+    /// 从 QueueURL 中截取队列名称
+    pub fn queue_name(&self) -> &str {
+        self.queue_url
+            .rsplit_once('/')
+            .map_or(self.queue_url.as_str(), |(_, name)| name)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename = "Queues")]
+pub struct QueueListResponse {
+    #[serde(rename = "Queue", default)]
+    pub queues: Vec<QueueUrl>,
+    /// 为空表示已经是最后一页
+    #[serde(rename = "NextMarker")]
+    pub next_marker: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename = "Queues")]
+pub struct QueueDetailListResponse {
+    #[serde(rename = "Queue", default)]
+    pub queues: Vec<QueueAttribute>,
+    /// 为空表示已经是最后一页
+    #[serde(rename = "NextMarker")]
+    pub next_marker: Option<String>,
+}
+
+/// [`QueueManager::ensure_queue`] 的结果
+#[derive(Debug, Clone)]
+pub enum EnsureQueueOutcome {
+    /// 新创建了队列
+    Created,
+    /// 队列已存在且属性相同
+    AlreadyExistsSame,
+    /// 队列已存在但属性不同
+    Conflict {
+        existing: QueueAttribute,
+        diff: Vec<AttributeChange>,
+    },
+}
+
+/// 按 NextMarker 逐页请求，直到 NextMarker 为空
+fn pages<T, F, Fut>(r: ListQueueRequest, f: F) -> impl Stream<Item = Result<T>>
+where
+    F: Fn(ListQueueRequest) -> Fut,
+    Fut: Future<Output = Result<(Vec<T>, Option<String>)>>,
+{
+    stream::try_unfold((Some(r), f), |(r, f)| async move {
+        let Some(r) = r else {
+            return Ok::<_, Error>(None);
+        };
+        let (items, next_marker) = f(r.clone()).await?;
+        let next = next_marker
+            .filter(|m| !m.is_empty())
+            .map(|marker| ListQueueRequest {
+                marker: Some(marker),
+                ..r
+            });
+        Ok(Some((stream::iter(items.into_iter().map(Ok)), (next, f))))
+    })
+    .try_flatten()
+}
+
 /// 队列管理实例
 /// https://help.aliyun.com/document_detail/140734.html
 #[derive(Debug, Clone)]
@@ -89,23 +299,91 @@ impl QueueManager {
         Self { client: c.clone() }
     }
 
-    // pub async fn list_queues(&self) -> Result<Vec<Queue>> {
-    //     let (status_code, v) = self
-    //         .client
-    //         .request("/queues", "GET", "application/xml", "")
-    //         .await?;
-    //     if status_code.is_success() {
-    //         let res: QueueListResponse =
-    //             serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeResponseFailed)?;
-    //         Ok(res.queues)
-    //     } else {
-    //         let res: ErrorResponse =
-    //             serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeErrorResponseFailed)?;
-    //         Err(res.into())
-    //     }
-    // }
-    //
+    /// 调用ListQueue接口分页列出队列
+    pub async fn list_queues(&self, r: &ListQueueRequest) -> Result<QueueListResponse> {
+        let v = self.list(r, false).await?;
+        let res: QueueListResponse =
+            serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeResponseFailed)?;
+        Ok(res)
+    }
+
+    /// 与 [`QueueManager::list_queues`] 相同，同时返回每个队列的属性
+    pub async fn list_queues_detail(
+        &self,
+        r: &ListQueueRequest,
+    ) -> Result<QueueDetailListResponse> {
+        let v = self.list(r, true).await?;
+        let res: QueueDetailListResponse =
+            serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeResponseFailed)?;
+        Ok(res)
+    }
+
+    /// 从 r.marker 开始逐页列出所有队列
+    pub fn queue_stream(&self, r: ListQueueRequest) -> impl Stream<Item = Result<QueueUrl>> {
+        let qm = self.clone();
+        pages(r, move |r| {
+            let qm = qm.clone();
+            async move {
+                let res = qm.list_queues(&r).await?;
+                Ok((res.queues, res.next_marker))
+            }
+        })
+    }
+
+    /// 从 r.marker 开始逐页列出所有队列及其属性
+    pub fn queue_detail_stream(
+        &self,
+        r: ListQueueRequest,
+    ) -> impl Stream<Item = Result<QueueAttribute>> {
+        let qm = self.clone();
+        pages(r, move |r| {
+            let qm = qm.clone();
+            async move {
+                let res = qm.list_queues_detail(&r).await?;
+                Ok((res.queues, res.next_marker))
+            }
+        })
+    }
+
+    async fn list(&self, r: &ListQueueRequest, with_meta: bool) -> Result<Vec<u8>> {
+        let headers = r.headers(with_meta);
+        let headers: Vec<(&str, &str)> = headers.iter().map(|(k, v)| (*k, v.as_str())).collect();
+        let (status_code, v) = self
+            .client
+            .request_with_headers("/queues", "GET", "application/xml", "", None, &headers)
+            .await?;
+        if status_code.is_success() {
+            Ok(v)
+        } else {
+            let res: ErrorResponse =
+                serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeErrorResponseFailed)?;
+            Err(res.into())
+        }
+    }
+
     pub async fn create_queue(&self, q: &CreateQueueRequest) -> Result<()> {
+        self.create(q).await.map(|_| ())
+    }
+
+    /// 幂等地创建队列，队列已存在时区分属性是否相同
+    /// 属性不同时返回线上队列的属性以及与 q 的差异
+    pub async fn ensure_queue(&self, q: &CreateQueueRequest) -> Result<EnsureQueueOutcome> {
+        match self.create(q).await {
+            Ok(StatusCode::NO_CONTENT) | Err(Error::MNSQueueAlreadyExistAndHaveSameAttr(_)) => {
+                Ok(EnsureQueueOutcome::AlreadyExistsSame)
+            }
+            Ok(_) => Ok(EnsureQueueOutcome::Created),
+            Err(Error::MNSQueueAlreadyExist(_)) => {
+                let existing = self.get_queue_attributes(&q.queue_name).await?;
+                let diff = q.diff(&existing);
+                Ok(EnsureQueueOutcome::Conflict { existing, diff })
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// 创建成功时返回 201，队列已存在且属性相同时返回 204
+    async fn create(&self, q: &CreateQueueRequest) -> Result<StatusCode> {
         let (status_code, v) = self
             .client
             .request(
@@ -113,11 +391,11 @@ impl QueueManager {
                 "PUT",
                 "application/xml",
                 &serde_xml_rs::to_string(q).unwrap(),
-                Some(5),
+                None,
             )
             .await?;
         if status_code.is_success() {
-            Ok(())
+            Ok(status_code)
         } else {
             let res: ErrorResponse =
                 serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeErrorResponseFailed)?;
@@ -132,7 +410,32 @@ impl QueueManager {
                 "DELETE",
                 "application/xml",
                 "",
-                Some(5),
+                None,
+            )
+            .await?;
+        if status_code.is_success() {
+            Ok(())
+        } else {
+            let res: ErrorResponse =
+                serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeErrorResponseFailed)?;
+            Err(res.into())
+        }
+    }
+
+    /// 调用SetQueueAttributes接口修改队列的属性
+    pub async fn set_queue_attributes(
+        &self,
+        name: &str,
+        attr: &SetQueueAttributesRequest,
+    ) -> Result<()> {
+        let (status_code, v) = self
+            .client
+            .request(
+                &format!("/queues/{name}?metaoverride=true"),
+                "PUT",
+                "application/xml",
+                &serde_xml_rs::to_string(attr).unwrap(),
+                None,
             )
             .await?;
         if status_code.is_success() {
@@ -152,7 +455,7 @@ impl QueueManager {
                 "GET",
                 "application/xml",
                 "",
-                Some(5),
+                None,
             )
             .await?;
 
@@ -172,6 +475,7 @@ impl QueueManager {
 mod test {
     use super::*;
     use crate::devtool::{get_client, get_conf};
+    use futures::StreamExt;
 
     #[tokio::test]
     async fn test_queue_manager() {
@@ -187,4 +491,71 @@ mod test {
         .unwrap();
         dbg!(qm.get_queue_attributes("sstest").await.unwrap());
     }
+
+    #[test]
+    fn test_serde() {
+        let src = r#"<?xml version="1.0" encoding="UTF-8"?><Queue><VisibilityTimeout>60</VisibilityTimeout><PollingWaitSeconds>30</PollingWaitSeconds><LoggingEnabled>True</LoggingEnabled></Queue>"#;
+        let r = SetQueueAttributesRequest {
+            visibility_timeout: Some(60),
+            polling_wait_seconds: Some(30),
+            logging_enabled: Some(true),
+            ..SetQueueAttributesRequest::default()
+        };
+        assert_eq!(src, serde_xml_rs::to_string(&r).unwrap());
+
+        let src = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Queue xmlns="http://mns.aliyuncs.com/doc/v1/">
+  <QueueName>q1</QueueName>
+  <CreateTime>1250700999</CreateTime>
+  <LastModifyTime>1250700999</LastModifyTime>
+  <VisibilityTimeout>60</VisibilityTimeout>
+  <MaximumMessageSize>65536</MaximumMessageSize>
+  <MessageRetentionPeriod>345600</MessageRetentionPeriod>
+  <DelaySeconds>0</DelaySeconds>
+  <PollingWaitSeconds>0</PollingWaitSeconds>
+  <ActiveMessages>20</ActiveMessages>
+  <InactiveMessages>0</InactiveMessages>
+  <DelayMessages>0</DelayMessages>
+  <LoggingEnabled>True</LoggingEnabled>
+</Queue>"#;
+        let a: QueueAttribute = serde_xml_rs::from_str(src).unwrap();
+        assert_eq!(60, a.visibility_timeout);
+        assert_eq!(20, a.active_messages);
+        assert!(a.logging_enabled);
+    }
+
+    #[test]
+    fn test_list_response() {
+        let src = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Queues xmlns="http://mns.aliyuncs.com/doc/v1/">
+  <Queue><QueueURL>http://1234.mns.cn-hangzhou.aliyuncs.com/queues/q1</QueueURL></Queue>
+  <Queue><QueueURL>http://1234.mns.cn-hangzhou.aliyuncs.com/queues/q2</QueueURL></Queue>
+  <NextMarker>q3</NextMarker>
+</Queues>"#;
+        let r: QueueListResponse = serde_xml_rs::from_str(src).unwrap();
+        let names: Vec<_> = r.queues.iter().map(|q| q.queue_name()).collect();
+        assert_eq!(vec!["q1", "q2"], names);
+        assert_eq!(Some("q3".to_string()), r.next_marker);
+    }
+
+    #[tokio::test]
+    async fn test_pages() {
+        let s = pages(
+            ListQueueRequest {
+                prefix: Some("q".to_string()),
+                ..ListQueueRequest::default()
+            },
+            |r| async move {
+                assert_eq!(Some("q".to_string()), r.prefix);
+                match r.marker.as_deref() {
+                    None => Ok((vec![1, 2], Some("m1".to_string()))),
+                    Some("m1") => Ok((vec![], Some("m2".to_string()))),
+                    Some("m2") => Ok((vec![3], Some("".to_string()))),
+                    Some(m) => panic!("unexpected marker {m}"),
+                }
+            },
+        );
+        let items: Vec<i32> = s.map(|r| r.unwrap()).collect().await;
+        assert_eq!(vec![1, 2, 3], items);
+    }
 }
@@ -0,0 +1,175 @@
+//! 请求失败后的重试策略，由 [`crate::Client`] 应用到每一个请求
+//!
+//! 失败分为三类，见 [`RetryClass`]。SendMessage、PublishMessage 等 POST 请求不是幂等的，
+//! 只在确定服务端没有处理请求时重试，例如连接失败或 `QpsLimitExceeded`
+//!
+//! 需要为单次调用使用不同的策略时，用 [`crate::Client::with_retry_policy`] 得到一个新的 Client
+//!
+//! 重试间隔使用 tokio 的定时器等待，没有开启 `tokio` feature 时不重试；
+//! 批量接口部分失败时返回的逐条结果不会重试，由调用方处理
+use http::{Method, StatusCode};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// 一次失败的请求
+#[derive(Debug)]
+pub enum Failure<'a> {
+    /// 没有收到响应，例如连接失败、超时、连接被重置
+    Transport(&'a anyhow::Error),
+    /// 服务端返回了错误，code 为错误响应中的 Code
+    Response {
+        status: StatusCode,
+        code: Option<&'a str>,
+    },
+}
+
+/// 失败的分类
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryClass {
+    /// 不重试
+    Fatal,
+    /// 服务端没有处理请求，所有请求都可以重试
+    Safe,
+    /// 服务端可能已经处理了请求，只重试幂等的请求
+    Idempotent,
+}
+
+/// 默认的分类方式
+pub fn default_classify(f: &Failure<'_>) -> RetryClass {
+    match f {
+        #[cfg(feature = "reqwest")]
+        Failure::Transport(e)
+            if e.downcast_ref::<reqwest::Error>()
+                .is_some_and(|e| e.is_connect()) =>
+        {
+            RetryClass::Safe
+        }
+        Failure::Transport(_) => RetryClass::Idempotent,
+        Failure::Response {
+            code: Some("QpsLimitExceeded"),
+            ..
+        } => RetryClass::Safe,
+        Failure::Response {
+            code: Some("InternalError"),
+            ..
+        } => RetryClass::Idempotent,
+        Failure::Response { status, .. } if status.is_server_error() => RetryClass::Idempotent,
+        Failure::Response { .. } => RetryClass::Fatal,
+    }
+}
+
+/// 重试策略，等待时间按指数增长：base_delay * 2^(n-1)，不超过 max_delay
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// 最多请求几次，包括第一次，1 表示不重试
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// 开启后在 [0, 等待时间] 之间随机等待，避免大量客户端同时重试
+    pub jitter: bool,
+    pub classify: fn(&Failure<'_>) -> RetryClass,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(5),
+            jitter: true,
+            classify: default_classify,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// 不重试
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// 第 attempt 次请求失败后是否重试，attempt 从 1 开始
+    pub(crate) fn should_retry(&self, attempt: u32, method: &Method, f: &Failure<'_>) -> bool {
+        if attempt >= self.max_attempts {
+            return false;
+        }
+        match (self.classify)(f) {
+            RetryClass::Fatal => false,
+            RetryClass::Safe => true,
+            RetryClass::Idempotent => method.is_idempotent(),
+        }
+    }
+
+    /// 第 attempt 次请求失败后的等待时间
+    pub(crate) fn delay(&self, attempt: u32) -> Duration {
+        let exp = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)));
+        let d = exp.min(self.max_delay);
+        if self.jitter {
+            d.mul_f64(random_fraction())
+        } else {
+            d
+        }
+    }
+}
+
+/// [0, 1) 之间的随机数，只用于打散重试时间
+fn random_fraction() -> f64 {
+    let mut h = RandomState::new().build_hasher();
+    h.write_u64(0);
+    (h.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_should_retry() {
+        let p = RetryPolicy::default();
+        let qps = Failure::Response {
+            status: StatusCode::SERVICE_UNAVAILABLE,
+            code: Some("QpsLimitExceeded"),
+        };
+        let internal = Failure::Response {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            code: Some("InternalError"),
+        };
+        let not_found = Failure::Response {
+            status: StatusCode::NOT_FOUND,
+            code: Some("QueueNotExist"),
+        };
+        assert!(p.should_retry(1, &Method::POST, &qps));
+        assert!(!p.should_retry(1, &Method::POST, &internal));
+        assert!(p.should_retry(2, &Method::GET, &internal));
+        assert!(!p.should_retry(3, &Method::GET, &internal));
+        assert!(!p.should_retry(1, &Method::GET, &not_found));
+
+        let e = anyhow::anyhow!("connection reset");
+        assert!(!p.should_retry(1, &Method::POST, &Failure::Transport(&e)));
+        assert!(p.should_retry(1, &Method::DELETE, &Failure::Transport(&e)));
+        assert!(!RetryPolicy::none().should_retry(1, &Method::GET, &internal));
+    }
+
+    #[test]
+    fn test_delay() {
+        let p = RetryPolicy {
+            jitter: false,
+            ..RetryPolicy::default()
+        };
+        assert_eq!(Duration::from_millis(100), p.delay(1));
+        assert_eq!(Duration::from_millis(400), p.delay(3));
+        assert_eq!(Duration::from_secs(5), p.delay(20));
+        assert_eq!(Duration::from_secs(5), p.delay(100));
+
+        let p = RetryPolicy::default();
+        for i in 1..10 {
+            assert!(p.delay(i) <= Duration::from_secs(5));
+        }
+    }
+}
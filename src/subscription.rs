@@ -0,0 +1,382 @@
+//! 订阅相关的类型，订阅操作见 [`crate::topic::TopicOperation`]
+use serde::de::Error as _;
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// 订阅的推送地址
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Endpoint {
+    /// 推送到队列，`acs:mns:{region}:{account_id}:queues/{queue_name}`
+    Queue {
+        region: String,
+        account_id: String,
+        queue_name: String,
+    },
+    /// 推送到 HTTP 地址，`http://` 或 `https://` 开头
+    Http(String),
+    /// 推送到邮箱，`mail:directmail:{address}`
+    Mail(String),
+    /// 推送到手机短信，`sms:directsms:{phone}`
+    /// 为 None 时使用 `sms:directsms:anonymous`，由消息属性指定接收号码
+    Sms(Option<String>),
+    /// 无法识别的推送地址，原样保留，避免新的地址类型导致查询订阅失败
+    Other(String),
+}
+
+impl Display for Endpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Endpoint::Queue {
+                region,
+                account_id,
+                queue_name,
+            } => write!(f, "acs:mns:{region}:{account_id}:queues/{queue_name}"),
+            Endpoint::Http(url) => write!(f, "{url}"),
+            Endpoint::Mail(address) => write!(f, "mail:directmail:{address}"),
+            Endpoint::Sms(Some(phone)) => write!(f, "sms:directsms:{phone}"),
+            Endpoint::Sms(None) => write!(f, "sms:directsms:anonymous"),
+            Endpoint::Other(s) => write!(f, "{s}"),
+        }
+    }
+}
+
+impl FromStr for Endpoint {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with("http://") || s.starts_with("https://") {
+            return Ok(Endpoint::Http(s.to_string()));
+        }
+        if let Some(address) = s.strip_prefix("mail:directmail:") {
+            return Ok(Endpoint::Mail(address.to_string()));
+        }
+        if let Some(phone) = s.strip_prefix("sms:directsms:") {
+            return Ok(Endpoint::Sms(match phone {
+                "anonymous" => None,
+                phone => Some(phone.to_string()),
+            }));
+        }
+        if let Some(rest) = s.strip_prefix("acs:mns:") {
+            let mut parts = rest.splitn(3, ':');
+            if let (Some(region), Some(account_id), Some(queue)) =
+                (parts.next(), parts.next(), parts.next())
+            {
+                if let Some(queue_name) = queue.strip_prefix("queues/") {
+                    return Ok(Endpoint::Queue {
+                        region: region.to_string(),
+                        account_id: account_id.to_string(),
+                        queue_name: queue_name.to_string(),
+                    });
+                }
+            }
+        }
+        Ok(Endpoint::Other(s.to_string()))
+    }
+}
+
+impl Serialize for Endpoint {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Endpoint {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(D::Error::custom)
+    }
+}
+
+/// 订阅的过滤标签，只有 MessageTag 与之相同的消息才会推送给该订阅
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct FilterTag(pub String);
+
+impl From<&str> for FilterTag {
+    fn from(value: &str) -> Self {
+        Self(value.to_string())
+    }
+}
+
+impl FilterTag {
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+/// 推送失败时的重试策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NotifyStrategy {
+    /// 重试 3 次，每次间隔 10~20 秒
+    #[serde(rename = "BACKOFF_RETRY")]
+    BackoffRetry,
+    /// 重试 176 次，间隔按指数递增，总计 1 天
+    #[serde(rename = "EXPONENTIAL_DECAY_RETRY")]
+    ExponentialDecayRetry,
+}
+
+impl NotifyStrategy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotifyStrategy::BackoffRetry => "BACKOFF_RETRY",
+            NotifyStrategy::ExponentialDecayRetry => "EXPONENTIAL_DECAY_RETRY",
+        }
+    }
+}
+
+/// 推送的消息格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NotifyContentFormat {
+    #[serde(rename = "XML")]
+    Xml,
+    #[serde(rename = "JSON")]
+    Json,
+    /// 只推送消息正文
+    #[serde(rename = "SIMPLIFIED")]
+    Simplified,
+}
+
+impl NotifyContentFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotifyContentFormat::Xml => "XML",
+            NotifyContentFormat::Json => "JSON",
+            NotifyContentFormat::Simplified => "SIMPLIFIED",
+        }
+    }
+}
+
+/// 创建订阅
+#[derive(Debug, Clone)]
+pub struct SubscribeRequest {
+    pub subscription_name: String,
+    pub endpoint: Endpoint,
+    pub filter_tag: Option<FilterTag>,
+    pub notify_strategy: Option<NotifyStrategy>,
+    pub notify_content_format: Option<NotifyContentFormat>,
+}
+impl Serialize for SubscribeRequest {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut msg = serializer.serialize_struct("Subscription", 4)?;
+        msg.serialize_field("Endpoint", &self.endpoint)?;
+        if let Some(v) = &self.filter_tag {
+            msg.serialize_field("FilterTag", v)?;
+        }
+        if let Some(v) = &self.notify_strategy {
+            msg.serialize_field("NotifyStrategy", v.as_str())?;
+        }
+        if let Some(v) = &self.notify_content_format {
+            msg.serialize_field("NotifyContentFormat", v.as_str())?;
+        }
+        msg.end()
+    }
+}
+
+/// 修改订阅属性，目前只支持修改重试策略
+#[derive(Debug, Clone, Default)]
+pub struct SetSubscriptionAttributesRequest {
+    pub notify_strategy: Option<NotifyStrategy>,
+}
+impl Serialize for SetSubscriptionAttributesRequest {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut msg = serializer.serialize_struct("Subscription", 1)?;
+        if let Some(v) = &self.notify_strategy {
+            msg.serialize_field("NotifyStrategy", v.as_str())?;
+        }
+        msg.end()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename = "Subscription")]
+pub struct SubscriptionAttribute {
+    #[serde(rename = "SubscriptionName")]
+    pub subscription_name: String,
+    /// 订阅者的账号 ID
+    #[serde(rename = "Subscriber")]
+    pub subscriber: String,
+    /// 主题所有者的账号 ID
+    #[serde(rename = "TopicOwner")]
+    pub topic_owner: String,
+    #[serde(rename = "TopicName")]
+    pub topic_name: String,
+    #[serde(rename = "Endpoint")]
+    pub endpoint: Endpoint,
+    #[serde(rename = "NotifyStrategy")]
+    pub notify_strategy: NotifyStrategy,
+    #[serde(rename = "NotifyContentFormat")]
+    pub notify_content_format: NotifyContentFormat,
+    #[serde(rename = "FilterTag")]
+    pub filter_tag: Option<FilterTag>,
+    #[serde(rename = "CreateTime")]
+    pub create_time: i64,
+    #[serde(rename = "LastModifyTime")]
+    pub last_modify_time: i64,
+}
+
+/// 分页列出订阅的参数
+#[derive(Debug, Clone, Default)]
+pub struct ListSubscriptionRequest {
+    /// 按照订阅名称前缀查询
+    pub prefix: Option<String>,
+    /// 上一次请求返回的 NextMarker
+    pub marker: Option<String>,
+    /// 单次返回的最大个数，取值范围 1~1000，默认 1000
+    pub ret_number: Option<u32>,
+}
+
+impl ListSubscriptionRequest {
+    pub(crate) fn headers(&self) -> Vec<(&str, String)> {
+        let mut headers = vec![];
+        if let Some(v) = &self.prefix {
+            headers.push(("x-mns-prefix", v.clone()));
+        }
+        if let Some(v) = &self.marker {
+            headers.push(("x-mns-marker", v.clone()));
+        }
+        if let Some(v) = self.ret_number {
+            headers.push(("x-mns-ret-number", v.to_string()));
+        }
+        headers
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename = "Subscription")]
+pub struct SubscriptionUrl {
+    #[serde(rename = "SubscriptionURL")]
+    pub subscription_url: String,
+}
+
+impl SubscriptionUrl {
+    /// 从 SubscriptionURL 中截取订阅名称
+    pub fn subscription_name(&self) -> &str {
+        self.subscription_url
+            .rsplit_once('/')
+            .map_or(self.subscription_url.as_str(), |(_, name)| name)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename = "Subscriptions")]
+pub struct SubscriptionListResponse {
+    #[serde(rename = "Subscription", default)]
+    pub subscriptions: Vec<SubscriptionUrl>,
+    /// 为空表示已经是最后一页
+    #[serde(rename = "NextMarker")]
+    pub next_marker: Option<String>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_xml_rs::to_string;
+
+    #[test]
+    fn test_endpoint() {
+        for (s, e) in [
+            (
+                "acs:mns:cn-hangzhou:1234:queues/q1",
+                Endpoint::Queue {
+                    region: "cn-hangzhou".to_string(),
+                    account_id: "1234".to_string(),
+                    queue_name: "q1".to_string(),
+                },
+            ),
+            (
+                "https://example.com/notify",
+                Endpoint::Http("https://example.com/notify".to_string()),
+            ),
+            (
+                "mail:directmail:a@b.com",
+                Endpoint::Mail("a@b.com".to_string()),
+            ),
+            ("sms:directsms:anonymous", Endpoint::Sms(None)),
+            (
+                "sms:directsms:13800000000",
+                Endpoint::Sms(Some("13800000000".to_string())),
+            ),
+        ] {
+            assert_eq!(e, s.parse().unwrap());
+            assert_eq!(s, e.to_string());
+        }
+        for s in ["ftp://x", "acs:mns:cn-hangzhou:1234:topics/t"] {
+            let e: Endpoint = s.parse().unwrap();
+            assert_eq!(Endpoint::Other(s.to_string()), e);
+            assert_eq!(s, e.to_string());
+        }
+    }
+
+    #[test]
+    fn test_serde() {
+        let src = r#"<?xml version="1.0" encoding="UTF-8"?><Subscription><Endpoint>http://example.com/notify</Endpoint><FilterTag>important</FilterTag><NotifyStrategy>EXPONENTIAL_DECAY_RETRY</NotifyStrategy><NotifyContentFormat>SIMPLIFIED</NotifyContentFormat></Subscription>"#;
+        let r = SubscribeRequest {
+            subscription_name: "s".to_string(),
+            endpoint: Endpoint::Http("http://example.com/notify".to_string()),
+            filter_tag: Some("important".into()),
+            notify_strategy: Some(NotifyStrategy::ExponentialDecayRetry),
+            notify_content_format: Some(NotifyContentFormat::Simplified),
+        };
+        assert_eq!(src, to_string(&r).unwrap());
+
+        let src = r#"<?xml version="1.0" encoding="UTF-8"?><Subscription><NotifyStrategy>BACKOFF_RETRY</NotifyStrategy></Subscription>"#;
+        let r = SetSubscriptionAttributesRequest {
+            notify_strategy: Some(NotifyStrategy::BackoffRetry),
+        };
+        assert_eq!(src, to_string(&r).unwrap());
+
+        let src = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Subscription xmlns="http://mns.aliyuncs.com/doc/v1/">
+  <SubscriptionName>s1</SubscriptionName>
+  <Subscriber>1234</Subscriber>
+  <TopicOwner>1234</TopicOwner>
+  <TopicName>t1</TopicName>
+  <Endpoint>acs:mns:cn-hangzhou:1234:queues/q1</Endpoint>
+  <NotifyStrategy>BACKOFF_RETRY</NotifyStrategy>
+  <NotifyContentFormat>XML</NotifyContentFormat>
+  <FilterTag>important</FilterTag>
+  <CreateTime>1449462140</CreateTime>
+  <LastModifyTime>1449462140</LastModifyTime>
+</Subscription>"#;
+        let a: SubscriptionAttribute = serde_xml_rs::from_str(src).unwrap();
+        assert_eq!("s1", a.subscription_name);
+        assert_eq!(
+            Endpoint::Queue {
+                region: "cn-hangzhou".to_string(),
+                account_id: "1234".to_string(),
+                queue_name: "q1".to_string(),
+            },
+            a.endpoint
+        );
+        assert_eq!(NotifyStrategy::BackoffRetry, a.notify_strategy);
+        assert_eq!(NotifyContentFormat::Xml, a.notify_content_format);
+        assert_eq!(Some(FilterTag::from("important")), a.filter_tag);
+
+        let src = src.replace(
+            "acs:mns:cn-hangzhou:1234:queues/q1",
+            "acs:dm:cn-hangzhou:1234:mail/a@b.com",
+        );
+        let a: SubscriptionAttribute = serde_xml_rs::from_str(&src).unwrap();
+        assert_eq!(
+            Endpoint::Other("acs:dm:cn-hangzhou:1234:mail/a@b.com".to_string()),
+            a.endpoint
+        );
+
+        let src = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Subscriptions xmlns="http://mns.aliyuncs.com/doc/v1/">
+  <Subscription><SubscriptionURL>http://1234.mns.cn-hangzhou.aliyuncs.com/topics/t1/subscriptions/s1</SubscriptionURL></Subscription>
+  <NextMarker>s2</NextMarker>
+</Subscriptions>"#;
+        let r: SubscriptionListResponse = serde_xml_rs::from_str(src).unwrap();
+        assert_eq!("s1", r.subscriptions[0].subscription_name());
+        assert_eq!(Some("s2".to_string()), r.next_marker);
+    }
+}
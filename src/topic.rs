@@ -0,0 +1,366 @@
+//! 主题消息操作 API，包括向主题发布消息以及订阅的管理
+use crate::client::Client;
+use crate::error::Error::{
+    DeserializeErrorResponseFailed, DeserializeResponseFailed, SerializeMessageFailed,
+};
+use crate::error::Result;
+use crate::queue::ErrorResponse;
+use crate::subscription::{
+    ListSubscriptionRequest, SetSubscriptionAttributesRequest, SubscribeRequest,
+    SubscriptionAttribute, SubscriptionListResponse,
+};
+use async_trait::async_trait;
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// 主题消息操作 API
+#[derive(Debug, Clone)]
+pub struct Topic {
+    /// 主题名称
+    pub name: String,
+    client: Client,
+}
+
+/// 发布到主题的消息
+#[derive(Debug, Clone, Default)]
+pub struct MessagePublishRequest {
+    pub message_body: String,
+    /// 消息标签，用于订阅的消息过滤
+    pub message_tag: Option<String>,
+    pub message_attributes: Option<MessageAttributes>,
+}
+impl Serialize for MessagePublishRequest {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut msg = serializer.serialize_struct("Message", 3)?;
+        msg.serialize_field("MessageBody", &self.message_body)?;
+        if let Some(t) = &self.message_tag {
+            msg.serialize_field("MessageTag", t)?;
+        }
+        if let Some(a) = &self.message_attributes {
+            msg.serialize_field("MessageAttributes", a)?;
+        }
+        msg.end()
+    }
+}
+
+/// 推送到邮件、短信、移动推送等订阅时使用的消息属性
+/// 每一项在报文中都是一段 JSON 文本
+#[derive(Debug, Clone, Default)]
+pub struct MessageAttributes {
+    pub direct_mail: Option<MailAttributes>,
+    pub direct_sms: Option<SmsAttributes>,
+    pub push: Option<PushAttributes>,
+}
+impl Serialize for MessageAttributes {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::Error;
+        let mut msg = serializer.serialize_struct("MessageAttributes", 3)?;
+        if let Some(v) = &self.direct_mail {
+            let v = serde_json::to_string(v).map_err(S::Error::custom)?;
+            msg.serialize_field("DirectMail", &v)?;
+        }
+        if let Some(v) = &self.direct_sms {
+            let v = serde_json::to_string(v).map_err(S::Error::custom)?;
+            msg.serialize_field("DirectSMS", &v)?;
+        }
+        if let Some(v) = &self.push {
+            let v = serde_json::to_string(v).map_err(S::Error::custom)?;
+            msg.serialize_field("Push", &v)?;
+        }
+        msg.end()
+    }
+}
+
+/// 邮件推送属性
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MailAttributes {
+    /// 发信地址
+    #[serde(rename = "AccountName")]
+    pub account_name: String,
+    #[serde(rename = "Subject")]
+    pub subject: String,
+    /// 0 为随机账号，1 为发信地址
+    #[serde(rename = "AddressType")]
+    pub address_type: u8,
+    /// 邮件正文是否为 HTML，0 否 1 是
+    #[serde(rename = "IsHtml")]
+    pub is_html: u8,
+    /// 是否使用控制台配置的回信地址，0 否 1 是
+    #[serde(rename = "ReplyToAddress")]
+    pub reply_to_address: u8,
+}
+
+/// 短信推送属性
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SmsAttributes {
+    /// 短信签名
+    #[serde(rename = "FreeSignName")]
+    pub free_sign_name: String,
+    /// 短信模板
+    #[serde(rename = "TemplateCode")]
+    pub template_code: String,
+    /// `singleContent` 或 `multiContent`
+    #[serde(rename = "Type")]
+    pub sms_type: String,
+    /// 接收短信的手机号，多个号码用逗号分隔
+    #[serde(rename = "Receiver")]
+    pub receiver: String,
+    /// 短信模板参数，JSON 字符串
+    #[serde(rename = "SmsParams")]
+    pub sms_params: String,
+}
+
+/// 移动推送属性
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PushAttributes {
+    #[serde(rename = "AppKey")]
+    pub app_key: String,
+    /// 推送目标类型，例如 `DEVICE`、`ACCOUNT`、`ALIAS`、`TAG`、`ALL`
+    #[serde(rename = "Target")]
+    pub target: String,
+    #[serde(rename = "TargetValue")]
+    pub target_value: String,
+    /// 其余透传给推送服务的参数
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename = "Message")]
+pub struct MessagePublishResponse {
+    #[serde(rename = "MessageId")]
+    pub message_id: String,
+    #[serde(rename = "MessageBodyMD5")]
+    pub message_body_md5: String,
+}
+
+#[async_trait]
+pub trait TopicOperation {
+    async fn publish_message(&self, m: &MessagePublishRequest) -> Result<MessagePublishResponse>;
+    async fn subscribe(&self, r: &SubscribeRequest) -> Result<()>;
+    async fn unsubscribe(&self, subscription_name: &str) -> Result<()>;
+    async fn get_subscription_attributes(
+        &self,
+        subscription_name: &str,
+    ) -> Result<SubscriptionAttribute>;
+    async fn set_subscription_attributes(
+        &self,
+        subscription_name: &str,
+        attr: &SetSubscriptionAttributesRequest,
+    ) -> Result<()>;
+    async fn list_subscriptions(
+        &self,
+        r: &ListSubscriptionRequest,
+    ) -> Result<SubscriptionListResponse>;
+}
+
+impl Topic {
+    pub fn new(name: &str, c: &Client) -> Self {
+        Self {
+            name: name.to_string(),
+            client: c.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl TopicOperation for Topic {
+    /// 调用PublishMessage接口向指定的主题发布消息，消息会推送给所有匹配的订阅
+    async fn publish_message(&self, m: &MessagePublishRequest) -> Result<MessagePublishResponse> {
+        let (status_code, v) = self
+            .client
+            .request(
+                &format!("/topics/{}/messages", self.name),
+                "POST",
+                "application/xml",
+                &serde_xml_rs::to_string(m).map_err(SerializeMessageFailed)?,
+                None,
+            )
+            .await?;
+        if status_code.is_success() {
+            let res: MessagePublishResponse =
+                serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeResponseFailed)?;
+            Ok(res)
+        } else {
+            let res: ErrorResponse =
+                serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeErrorResponseFailed)?;
+            Err(res.into())
+        }
+    }
+
+    /// 调用Subscribe接口创建订阅
+    /// 订阅已存在且属性不同时返回 MNSSubscriptionAlreadyExist
+    async fn subscribe(&self, r: &SubscribeRequest) -> Result<()> {
+        let (status_code, v) = self
+            .client
+            .request(
+                &format!(
+                    "/topics/{}/subscriptions/{}",
+                    self.name, r.subscription_name
+                ),
+                "PUT",
+                "application/xml",
+                &serde_xml_rs::to_string(r).map_err(SerializeMessageFailed)?,
+                None,
+            )
+            .await?;
+        if status_code.is_success() {
+            Ok(())
+        } else {
+            let res: ErrorResponse =
+                serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeErrorResponseFailed)?;
+            Err(res.into())
+        }
+    }
+
+    /// 调用Unsubscribe接口删除订阅
+    async fn unsubscribe(&self, subscription_name: &str) -> Result<()> {
+        let (status_code, v) = self
+            .client
+            .request(
+                &format!("/topics/{}/subscriptions/{}", self.name, subscription_name),
+                "DELETE",
+                "application/xml",
+                "",
+                None,
+            )
+            .await?;
+        if status_code.is_success() {
+            Ok(())
+        } else {
+            let res: ErrorResponse =
+                serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeErrorResponseFailed)?;
+            Err(res.into())
+        }
+    }
+
+    /// 调用GetSubscriptionAttributes接口获取订阅的属性
+    async fn get_subscription_attributes(
+        &self,
+        subscription_name: &str,
+    ) -> Result<SubscriptionAttribute> {
+        let (status_code, v) = self
+            .client
+            .request(
+                &format!("/topics/{}/subscriptions/{}", self.name, subscription_name),
+                "GET",
+                "application/xml",
+                "",
+                None,
+            )
+            .await?;
+        if status_code.is_success() {
+            let res: SubscriptionAttribute =
+                serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeResponseFailed)?;
+            Ok(res)
+        } else {
+            let res: ErrorResponse =
+                serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeErrorResponseFailed)?;
+            Err(res.into())
+        }
+    }
+
+    /// 调用SetSubscriptionAttributes接口修改订阅的属性
+    async fn set_subscription_attributes(
+        &self,
+        subscription_name: &str,
+        attr: &SetSubscriptionAttributesRequest,
+    ) -> Result<()> {
+        let (status_code, v) = self
+            .client
+            .request(
+                &format!(
+                    "/topics/{}/subscriptions/{}?metaoverride=true",
+                    self.name, subscription_name
+                ),
+                "PUT",
+                "application/xml",
+                &serde_xml_rs::to_string(attr).map_err(SerializeMessageFailed)?,
+                None,
+            )
+            .await?;
+        if status_code.is_success() {
+            Ok(())
+        } else {
+            let res: ErrorResponse =
+                serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeErrorResponseFailed)?;
+            Err(res.into())
+        }
+    }
+
+    /// 调用ListSubscriptionByTopic接口分页列出主题下的订阅
+    async fn list_subscriptions(
+        &self,
+        r: &ListSubscriptionRequest,
+    ) -> Result<SubscriptionListResponse> {
+        let headers = r.headers();
+        let headers: Vec<(&str, &str)> = headers.iter().map(|(k, v)| (*k, v.as_str())).collect();
+        let (status_code, v) = self
+            .client
+            .request_with_headers(
+                &format!("/topics/{}/subscriptions", self.name),
+                "GET",
+                "application/xml",
+                "",
+                None,
+                &headers,
+            )
+            .await?;
+        if status_code.is_success() {
+            let res: SubscriptionListResponse =
+                serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeResponseFailed)?;
+            Ok(res)
+        } else {
+            let res: ErrorResponse =
+                serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeErrorResponseFailed)?;
+            Err(res.into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_xml_rs::to_string;
+
+    #[test]
+    fn test_serde() {
+        let src = r#"<?xml version="1.0" encoding="UTF-8"?><Message><MessageBody>aa</MessageBody></Message>"#;
+        let m = MessagePublishRequest {
+            message_body: "aa".to_string(),
+            ..MessagePublishRequest::default()
+        };
+        assert_eq!(src, to_string(&m).unwrap());
+
+        let src = r#"<?xml version="1.0" encoding="UTF-8"?><Message><MessageBody>aa</MessageBody><MessageTag>important</MessageTag><MessageAttributes><DirectMail>{"AccountName":"a@b.com","Subject":"hi","AddressType":1,"IsHtml":0,"ReplyToAddress":0}</DirectMail></MessageAttributes></Message>"#;
+        let m = MessagePublishRequest {
+            message_body: "aa".to_string(),
+            message_tag: Some("important".to_string()),
+            message_attributes: Some(MessageAttributes {
+                direct_mail: Some(MailAttributes {
+                    account_name: "a@b.com".to_string(),
+                    subject: "hi".to_string(),
+                    address_type: 1,
+                    ..MailAttributes::default()
+                }),
+                ..MessageAttributes::default()
+            }),
+        };
+        assert_eq!(src, to_string(&m).unwrap());
+
+        let src = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Message xmlns="http://mns.aliyuncs.com/doc/v1/">
+  <MessageId>765CB6E3D9C8A8A8-1-15F27D8CE7B-200000001</MessageId>
+  <MessageBodyMD5>C5DD56A39F5F7BB8B3337C6D11B6D8C7</MessageBodyMD5>
+</Message>"#;
+        let r: MessagePublishResponse = serde_xml_rs::from_str(src).unwrap();
+        assert_eq!("765CB6E3D9C8A8A8-1-15F27D8CE7B-200000001", r.message_id);
+    }
+}
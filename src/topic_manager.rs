@@ -0,0 +1,338 @@
+//! 主题管理实例
+
+use crate::client::Client;
+use crate::error::Error::{DeserializeErrorResponseFailed, DeserializeResponseFailed};
+use crate::error::Result;
+use crate::queue::ErrorResponse;
+use crate::xml::{bool_str, deserialize_bool};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename = "Topic")]
+pub struct TopicAttribute {
+    #[serde(rename = "TopicName")]
+    pub topic_name: String,
+    #[serde(rename = "CreateTime")]
+    pub create_time: i64,
+    #[serde(rename = "LastModifyTime")]
+    pub last_modify_time: i64,
+    #[serde(rename = "MaximumMessageSize")]
+    pub maximum_message_size: i32,
+    #[serde(rename = "MessageRetentionPeriod")]
+    pub message_retention_period: i32,
+    #[serde(rename = "MessageCount")]
+    pub message_count: i64,
+    #[serde(
+        rename = "LoggingEnabled",
+        default,
+        deserialize_with = "deserialize_bool"
+    )]
+    pub logging_enabled: bool,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CreateTopicRequest {
+    pub topic_name: String,
+    pub maximum_message_size: Option<i32>,
+    pub logging_enabled: Option<bool>,
+}
+impl Serialize for CreateTopicRequest {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut msg = serializer.serialize_struct("Topic", 2)?;
+        if let Some(v) = self.maximum_message_size {
+            msg.serialize_field("MaximumMessageSize", &v)?;
+        }
+        if let Some(v) = self.logging_enabled {
+            msg.serialize_field("LoggingEnabled", bool_str(v))?;
+        }
+        msg.end()
+    }
+}
+
+/// 修改主题属性，未设置的字段保持不变
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SetTopicAttributesRequest {
+    pub maximum_message_size: Option<i32>,
+    pub logging_enabled: Option<bool>,
+}
+impl Serialize for SetTopicAttributesRequest {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut msg = serializer.serialize_struct("Topic", 2)?;
+        if let Some(v) = self.maximum_message_size {
+            msg.serialize_field("MaximumMessageSize", &v)?;
+        }
+        if let Some(v) = self.logging_enabled {
+            msg.serialize_field("LoggingEnabled", bool_str(v))?;
+        }
+        msg.end()
+    }
+}
+
+/// 分页列出主题的参数
+#[derive(Debug, Clone, Default)]
+pub struct ListTopicRequest {
+    /// 按照主题名称前缀查询
+    pub prefix: Option<String>,
+    /// 上一次请求返回的 NextMarker
+    pub marker: Option<String>,
+    /// 单次返回的最大个数，取值范围 1~1000，默认 1000
+    pub ret_number: Option<u32>,
+}
+
+impl ListTopicRequest {
+    fn headers(&self, with_meta: bool) -> Vec<(&str, String)> {
+        let mut headers = vec![];
+        if let Some(v) = &self.prefix {
+            headers.push(("x-mns-prefix", v.clone()));
+        }
+        if let Some(v) = &self.marker {
+            headers.push(("x-mns-marker", v.clone()));
+        }
+        if let Some(v) = self.ret_number {
+            headers.push(("x-mns-ret-number", v.to_string()));
+        }
+        if with_meta {
+            headers.push(("x-mns-with-meta", "true".to_string()));
+        }
+        headers
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename = "Topic")]
+pub struct TopicUrl {
+    #[serde(rename = "TopicURL")]
+    pub topic_url: String,
+}
+
+impl TopicUrl {
+    /// 从 TopicURL 中截取主题名称
+    pub fn topic_name(&self) -> &str {
+        self.topic_url
+            .rsplit_once('/')
+            .map_or(self.topic_url.as_str(), |(_, name)| name)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename = "Topics")]
+pub struct TopicListResponse {
+    #[serde(rename = "Topic", default)]
+    pub topics: Vec<TopicUrl>,
+    /// 为空表示已经是最后一页
+    #[serde(rename = "NextMarker")]
+    pub next_marker: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename = "Topics")]
+pub struct TopicDetailListResponse {
+    #[serde(rename = "Topic", default)]
+    pub topics: Vec<TopicAttribute>,
+    /// 为空表示已经是最后一页
+    #[serde(rename = "NextMarker")]
+    pub next_marker: Option<String>,
+}
+
+/// 主题管理实例
+#[derive(Debug, Clone)]
+pub struct TopicManager {
+    client: Client,
+}
+
+impl TopicManager {
+    pub fn new(c: &Client) -> Self {
+        Self { client: c.clone() }
+    }
+
+    /// 调用CreateTopic接口创建一个新的主题
+    pub async fn create_topic(&self, t: &CreateTopicRequest) -> Result<()> {
+        let (status_code, v) = self
+            .client
+            .request(
+                &format!("/topics/{}", t.topic_name),
+                "PUT",
+                "application/xml",
+                &serde_xml_rs::to_string(t).unwrap(),
+                None,
+            )
+            .await?;
+        if status_code.is_success() {
+            Ok(())
+        } else {
+            let res: ErrorResponse =
+                serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeErrorResponseFailed)?;
+            Err(res.into())
+        }
+    }
+
+    /// 调用DeleteTopic接口删除一个已创建的主题
+    pub async fn delete_topic(&self, name: &str) -> Result<()> {
+        let (status_code, v) = self
+            .client
+            .request(
+                &format!("/topics/{name}"),
+                "DELETE",
+                "application/xml",
+                "",
+                None,
+            )
+            .await?;
+        if status_code.is_success() {
+            Ok(())
+        } else {
+            let res: ErrorResponse =
+                serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeErrorResponseFailed)?;
+            Err(res.into())
+        }
+    }
+
+    /// 调用GetTopicAttributes接口获取主题的属性
+    pub async fn get_topic_attributes(&self, name: &str) -> Result<TopicAttribute> {
+        let (status_code, v) = self
+            .client
+            .request(
+                &format!("/topics/{name}"),
+                "GET",
+                "application/xml",
+                "",
+                None,
+            )
+            .await?;
+        if status_code.is_success() {
+            let res: TopicAttribute =
+                serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeResponseFailed)?;
+            Ok(res)
+        } else {
+            let res: ErrorResponse =
+                serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeErrorResponseFailed)?;
+            Err(res.into())
+        }
+    }
+
+    /// 调用SetTopicAttributes接口修改主题的属性
+    pub async fn set_topic_attributes(
+        &self,
+        name: &str,
+        attr: &SetTopicAttributesRequest,
+    ) -> Result<()> {
+        let (status_code, v) = self
+            .client
+            .request(
+                &format!("/topics/{name}?metaoverride=true"),
+                "PUT",
+                "application/xml",
+                &serde_xml_rs::to_string(attr).unwrap(),
+                None,
+            )
+            .await?;
+        if status_code.is_success() {
+            Ok(())
+        } else {
+            let res: ErrorResponse =
+                serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeErrorResponseFailed)?;
+            Err(res.into())
+        }
+    }
+
+    /// 调用ListTopic接口分页列出主题
+    pub async fn list_topics(&self, r: &ListTopicRequest) -> Result<TopicListResponse> {
+        let v = self.list(r, false).await?;
+        let res: TopicListResponse =
+            serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeResponseFailed)?;
+        Ok(res)
+    }
+
+    /// 与 [`TopicManager::list_topics`] 相同，同时返回每个主题的属性
+    pub async fn list_topics_detail(
+        &self,
+        r: &ListTopicRequest,
+    ) -> Result<TopicDetailListResponse> {
+        let v = self.list(r, true).await?;
+        let res: TopicDetailListResponse =
+            serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeResponseFailed)?;
+        Ok(res)
+    }
+
+    async fn list(&self, r: &ListTopicRequest, with_meta: bool) -> Result<Vec<u8>> {
+        let headers = r.headers(with_meta);
+        let headers: Vec<(&str, &str)> = headers.iter().map(|(k, v)| (*k, v.as_str())).collect();
+        let (status_code, v) = self
+            .client
+            .request_with_headers("/topics", "GET", "application/xml", "", None, &headers)
+            .await?;
+        if status_code.is_success() {
+            Ok(v)
+        } else {
+            let res: ErrorResponse =
+                serde_xml_rs::from_reader(v.as_slice()).map_err(DeserializeErrorResponseFailed)?;
+            Err(res.into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_xml_rs::to_string;
+
+    #[test]
+    fn test_serde() {
+        let src = r#"<?xml version="1.0" encoding="UTF-8"?><Topic><MaximumMessageSize>1024</MaximumMessageSize><LoggingEnabled>True</LoggingEnabled></Topic>"#;
+        let t = CreateTopicRequest {
+            topic_name: "t".to_string(),
+            maximum_message_size: Some(1024),
+            logging_enabled: Some(true),
+        };
+        assert_eq!(src, to_string(&t).unwrap());
+
+        let src = r#"<?xml version="1.0" encoding="UTF-8"?><Topic><LoggingEnabled>False</LoggingEnabled></Topic>"#;
+        let t = SetTopicAttributesRequest {
+            maximum_message_size: None,
+            logging_enabled: Some(false),
+        };
+        assert_eq!(src, to_string(&t).unwrap());
+
+        let src = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Topic xmlns="http://mns.aliyuncs.com/doc/v1/">
+  <TopicName>MyTopic</TopicName>
+  <CreateTime>1449556005</CreateTime>
+  <LastModifyTime>1449556005</LastModifyTime>
+  <MaximumMessageSize>65536</MaximumMessageSize>
+  <MessageRetentionPeriod>86400</MessageRetentionPeriod>
+  <MessageCount>3</MessageCount>
+  <LoggingEnabled>True</LoggingEnabled>
+</Topic>"#;
+        let t: TopicAttribute = serde_xml_rs::from_str(src).unwrap();
+        assert_eq!("MyTopic", t.topic_name);
+        assert_eq!(65536, t.maximum_message_size);
+        assert_eq!(3, t.message_count);
+        assert!(t.logging_enabled);
+    }
+
+    #[test]
+    fn test_list_response() {
+        let src = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Topics xmlns="http://mns.aliyuncs.com/doc/v1/">
+  <Topic><TopicURL>http://1234.mns.cn-hangzhou.aliyuncs.com/topics/t1</TopicURL></Topic>
+  <Topic><TopicURL>http://1234.mns.cn-hangzhou.aliyuncs.com/topics/t2</TopicURL></Topic>
+  <NextMarker>t3</NextMarker>
+</Topics>"#;
+        let r: TopicListResponse = serde_xml_rs::from_str(src).unwrap();
+        let names: Vec<_> = r.topics.iter().map(|t| t.topic_name()).collect();
+        assert_eq!(vec!["t1", "t2"], names);
+        assert_eq!(Some("t3".to_string()), r.next_marker);
+
+        let src = r#"<?xml version="1.0" encoding="UTF-8"?><Topics xmlns="http://mns.aliyuncs.com/doc/v1/"></Topics>"#;
+        let r: TopicListResponse = serde_xml_rs::from_str(src).unwrap();
+        assert!(r.topics.is_empty());
+        assert_eq!(None, r.next_marker);
+    }
+}
@@ -0,0 +1,228 @@
+//! HTTP 传输层
+//! [`crate::Client`] 负责签名，签好的请求交给 [`Transport`] 发送，
+//! 默认使用 reqwest（`reqwest` feature），也可以接入自己的 HTTP 栈，
+//! 测试时可以使用 [`MemoryTransport`] 在进程内返回固定的响应
+use anyhow::Result;
+use async_trait::async_trait;
+use http::{HeaderMap, Method, StatusCode};
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// 已签名的请求
+#[derive(Debug, Clone)]
+pub struct TransportRequest {
+    pub method: Method,
+    /// endpoint 加上 resource
+    pub url: String,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+    pub timeout: Duration,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TransportResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+impl TransportResponse {
+    pub fn new(status: StatusCode, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status,
+            headers: HeaderMap::new(),
+            body: body.into(),
+        }
+    }
+}
+
+/// 发送已签名的请求
+#[async_trait]
+pub trait Transport: Debug + Send + Sync {
+    async fn send(&self, req: TransportRequest) -> Result<TransportResponse>;
+}
+
+/// 基于 reqwest 的实现
+#[cfg(feature = "reqwest")]
+#[derive(Debug, Clone, Default)]
+pub struct ReqwestTransport {
+    client: reqwest::Client,
+}
+
+#[cfg(feature = "reqwest")]
+impl ReqwestTransport {
+    pub fn new(client: reqwest::Client) -> Self {
+        Self { client }
+    }
+}
+
+#[cfg(feature = "reqwest")]
+#[async_trait]
+impl Transport for ReqwestTransport {
+    async fn send(&self, req: TransportRequest) -> Result<TransportResponse> {
+        // reqwest 0.11 使用 http 0.2，这里按字符串转换
+        let mut r = self
+            .client
+            .request(
+                reqwest::Method::from_bytes(req.method.as_str().as_bytes())?,
+                req.url.as_str(),
+            )
+            .timeout(req.timeout)
+            .body(req.body);
+        for (k, v) in &req.headers {
+            r = r.header(k.as_str(), v.as_bytes());
+        }
+        let res = r.send().await?;
+        let mut headers = HeaderMap::new();
+        for (k, v) in res.headers() {
+            headers.append(
+                http::HeaderName::from_bytes(k.as_str().as_bytes())?,
+                http::HeaderValue::from_bytes(v.as_bytes())?,
+            );
+        }
+        Ok(TransportResponse {
+            status: StatusCode::from_u16(res.status().as_u16())?,
+            headers,
+            body: res.bytes().await?.to_vec(),
+        })
+    }
+}
+
+type Handler = dyn Fn(&TransportRequest) -> TransportResponse + Send + Sync;
+
+/// 进程内的实现，由 handler 根据请求返回响应，并记录收到的请求
+#[derive(Clone)]
+pub struct MemoryTransport {
+    handler: Arc<Handler>,
+    requests: Arc<Mutex<Vec<TransportRequest>>>,
+}
+
+impl MemoryTransport {
+    pub fn new<F>(handler: F) -> Self
+    where
+        F: Fn(&TransportRequest) -> TransportResponse + Send + Sync + 'static,
+    {
+        Self {
+            handler: Arc::new(handler),
+            requests: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    /// 已收到的请求
+    pub fn requests(&self) -> Vec<TransportRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl Debug for MemoryTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MemoryTransport")
+            .field("requests", &self.requests.lock().unwrap().len())
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl Transport for MemoryTransport {
+    async fn send(&self, req: TransportRequest) -> Result<TransportResponse> {
+        let res = (self.handler)(&req);
+        self.requests.lock().unwrap().push(req);
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::queue::{MessageSendRequest, QueueOperation};
+    use crate::{Client, Queue};
+
+    #[tokio::test]
+    async fn test_memory_transport() {
+        let t = MemoryTransport::new(|req| match req.method {
+            Method::POST => TransportResponse::new(
+                StatusCode::CREATED,
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+<Message xmlns="http://mns.aliyuncs.com/doc/v1/">
+  <MessageId>5F290C926D472878-2-14D9529A8FA-200000002</MessageId>
+  <MessageBodyMD5>5D41402ABC4B2A76B9719D911017C592</MessageBodyMD5>
+</Message>"#,
+            ),
+            _ => TransportResponse::new(
+                StatusCode::NOT_FOUND,
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+<Error xmlns="http://mns.aliyuncs.com/doc/v1/">
+  <Code>MessageNotExist</Code>
+  <Message>Message not exist.</Message>
+  <RequestId>5F290C926D472878-2-14D9529A8FA-200000003</RequestId>
+  <HostId>http://123.mns.cn-hangzhou.aliyuncs.com</HostId>
+</Error>"#,
+            ),
+        });
+        let c = Client::with_transport("http://mns.test", "id", "sec", t.clone());
+        let q = Queue::new("q1", &c).with_md5_verification(true);
+        let r = q
+            .send_message(&MessageSendRequest {
+                message_body: "hello".to_string(),
+                ..MessageSendRequest::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!("5F290C926D472878-2-14D9529A8FA-200000002", r.message_id);
+        assert!(q.try_receive_message(Some(1)).await.unwrap().is_none());
+        assert!(q.batch_receive_message(16, None).await.unwrap().is_empty());
+
+        let reqs = t.requests();
+        assert_eq!(3, reqs.len());
+        assert_eq!("http://mns.test/queues/q1/messages", reqs[0].url);
+        assert!(reqs[0].headers["Authorization"]
+            .to_str()
+            .unwrap()
+            .starts_with("MNS id:"));
+        assert_eq!("2015-06-06", reqs[0].headers["x-mns-version"]);
+        assert_eq!(
+            "http://mns.test/queues/q1/messages?numOfMessages=16",
+            reqs[2].url
+        );
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        use crate::topic::{Topic, TopicOperation};
+
+        let t = MemoryTransport::new(|_| {
+            TransportResponse::new(
+                StatusCode::NOT_FOUND,
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+<Error xmlns="http://mns.aliyuncs.com/doc/v1/">
+  <Code>MessageNotExist</Code>
+  <Message>Message not exist.</Message>
+  <RequestId>5F290C926D472878-2-14D9529A8FA-200000003</RequestId>
+  <HostId>http://123.mns.cn-hangzhou.aliyuncs.com</HostId>
+</Error>"#,
+            )
+        });
+        let c = Client::with_transport("http://mns.test", "id", "sec", t.clone())
+            .with_timeout(Duration::from_secs(12));
+        let q = Queue::new("q1", &c);
+        let m = MessageSendRequest {
+            message_body: "hello".to_string(),
+            ..MessageSendRequest::default()
+        };
+        assert!(q.send_message(&m).await.is_err());
+        assert!(q.delete_message("h").await.is_err());
+        assert!(q.try_peek_message().await.unwrap().is_none());
+        assert!(Topic::new("t1", &c).unsubscribe("s1").await.is_err());
+        // 长轮询按等待时间加 1 秒
+        assert!(q.try_receive_message(Some(3)).await.unwrap().is_none());
+        assert!(q
+            .batch_receive_message(16, Some(20))
+            .await
+            .unwrap()
+            .is_empty());
+
+        let timeouts: Vec<_> = t.requests().iter().map(|r| r.timeout.as_secs()).collect();
+        assert_eq!(vec![12, 12, 12, 12, 4, 21], timeouts);
+    }
+}
@@ -0,0 +1,341 @@
+//! 带类型的队列，发送时序列化，消费时反序列化
+//!
+//! # Example
+//! ```rust,no_run
+//! use mns::codec::JsonCodec;
+//! use mns::consumer::ConsumeOptions;
+//! use mns::typed::{TypedDeliveryResult, TypedQueue};
+//! use mns::{Client, Queue};
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Debug, Serialize, Deserialize)]
+//! struct Order {
+//!     id: u64,
+//! }
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let client = Client::new("https://xxx.mns.cn-hangzhou.aliyuncs.com", "your id", "your key");
+//!     let queue: TypedQueue<Order, JsonCodec> =
+//!         TypedQueue::new(Queue::new("your queue name", &client));
+//!     queue.send(&Order { id: 1 }).await.unwrap();
+//!
+//!     let consumer = queue.consumer(ConsumeOptions::default());
+//!     consumer
+//!         .set_delegate(|msg: TypedDeliveryResult<Order>| async move {
+//!             let d = msg.unwrap().unwrap();
+//!             match &d.payload {
+//!                 Ok(order) => println!("{}", order.id),
+//!                 Err(e) => println!("bad message: {e}"),
+//!             }
+//!             d.delivery.ack().await.unwrap();
+//!         })
+//!         .await;
+//!     consumer.run();
+//! }
+//! ```
+use crate::codec::Codec;
+use crate::consumer::{
+    ConsumeOptions, Consumer, ConsumerDelegate, Delivery, DeliveryResult, UndecodableDelivery,
+};
+use crate::error::Result;
+use crate::queue::{BatchResult, MessageReceiveResponse, MessageSendRequest, MessageSendResponse};
+use crate::Queue;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::Arc;
+
+/// 以 C 序列化 T 的队列
+#[derive(Debug)]
+pub struct TypedQueue<T, C> {
+    queue: Queue,
+    _marker: PhantomData<fn() -> (T, C)>,
+}
+
+impl<T, C> Clone for TypedQueue<T, C> {
+    fn clone(&self) -> Self {
+        Self::new(self.queue.clone())
+    }
+}
+
+impl<T, C> TypedQueue<T, C> {
+    pub fn new(queue: Queue) -> Self {
+        Self {
+            queue,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn queue(&self) -> &Queue {
+        &self.queue
+    }
+}
+
+impl<T, C: Codec<T>> TypedQueue<T, C> {
+    pub async fn send(&self, value: &T) -> Result<MessageSendResponse> {
+        self.queue.send_bytes(&C::encode(value)?).await
+    }
+
+    /// 与 [`crate::queue::QueueOperation::batch_send_messages`] 相同，返回的结果与 values 一一对应
+    pub async fn batch_send(&self, values: &[T]) -> Result<Vec<BatchResult<MessageSendResponse>>> {
+        let data = values.iter().map(C::encode).collect::<Result<Vec<_>>>()?;
+        let bodies: Vec<&[u8]> = data.iter().map(Vec::as_slice).collect();
+        let ms = vec![MessageSendRequest::default(); values.len()];
+        self.queue.batch_send_bodies(&ms, &bodies).await
+    }
+
+    /// 反序列化通过 [`crate::queue::QueueOperation`] 收到的消息
+    pub fn decode(&self, m: &MessageReceiveResponse) -> Result<T> {
+        C::decode(&m.data)
+    }
+
+    pub fn consumer(&self, opt: ConsumeOptions) -> TypedConsumer<T, C> {
+        TypedConsumer {
+            consumer: Consumer::new(self.queue.clone(), opt),
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// 一条消息及其反序列化结果，正文解码或反序列化失败时仍然可以 ack / reject 原消息
+#[derive(Debug)]
+pub struct TypedDelivery<T> {
+    pub payload: Result<T>,
+    pub delivery: Delivery,
+}
+
+pub type TypedDeliveryResult<T> = anyhow::Result<Option<TypedDelivery<T>>>;
+
+/// 把消息反序列化后再交给 handler
+struct TypedDelegate<T, C, H> {
+    handler: Arc<H>,
+    _marker: PhantomData<fn() -> (T, C)>,
+}
+
+impl<T, C, H, F> ConsumerDelegate for TypedDelegate<T, C, H>
+where
+    T: Send + 'static,
+    C: Codec<T>,
+    F: Future<Output = ()> + Send + 'static,
+    H: Fn(TypedDeliveryResult<T>) -> F + Send + Sync + 'static,
+{
+    fn on_new_delivery(
+        &self,
+        delivery: DeliveryResult,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        let handler = self.handler.clone();
+        Box::pin(async move {
+            let delivery = match delivery {
+                Ok(Some(d)) => {
+                    let payload = match d.payload().await {
+                        Ok(data) => C::decode(&data),
+                        Err(e) => Err(e),
+                    };
+                    Ok(Some(TypedDelivery {
+                        payload,
+                        delivery: d,
+                    }))
+                }
+                Ok(None) => Ok(None),
+                // 正文解码失败时同样交出原消息，payload 为解码错误
+                Err(e) => match e.downcast::<UndecodableDelivery>() {
+                    Ok(u) => Ok(Some(TypedDelivery {
+                        payload: Err(u.error),
+                        delivery: u.delivery,
+                    })),
+                    Err(e) => Err(e),
+                },
+            };
+            handler(delivery).await
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct TypedConsumer<T, C> {
+    consumer: Consumer,
+    _marker: PhantomData<fn() -> (T, C)>,
+}
+
+impl<T, C> TypedConsumer<T, C>
+where
+    T: Send + 'static,
+    C: Codec<T> + 'static,
+{
+    #[cfg(feature = "tokio")]
+    pub async fn set_delegate<F, H>(&self, handler: H)
+    where
+        F: Future<Output = ()> + Send + 'static,
+        H: Fn(TypedDeliveryResult<T>) -> F + Send + Sync + 'static,
+    {
+        self.consumer
+            .set_delegate(TypedDelegate::<T, C, H> {
+                handler: Arc::new(handler),
+                _marker: PhantomData,
+            })
+            .await;
+    }
+
+    #[cfg(feature = "tokio")]
+    pub fn run(&self) {
+        self.consumer.run()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::Error::{DecodeMessageBodyFailed, DecodePayloadFailed};
+    use crate::queue::{BodyEncoding, QueueOperation};
+    use crate::transport::{MemoryTransport, TransportResponse};
+    use crate::Client;
+    use http::StatusCode;
+
+    /// 十进制文本，非数字的正文反序列化失败
+    struct TextCodec;
+
+    impl Codec<u64> for TextCodec {
+        fn encode(value: &u64) -> Result<Vec<u8>> {
+            Ok(value.to_string().into_bytes())
+        }
+
+        fn decode(data: &[u8]) -> Result<u64> {
+            std::str::from_utf8(data)
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .ok_or_else(|| DecodePayloadFailed(String::from_utf8_lossy(data).to_string()))
+        }
+    }
+
+    fn message(id: &str, body: &str) -> String {
+        format!(
+            "<Message><MessageId>{id}</MessageId><ReceiptHandle>h-{id}</ReceiptHandle>\
+             <MessageBodyMD5>0</MessageBodyMD5><MessageBody>{body}</MessageBody>\
+             <EnqueueTime>0</EnqueueTime><NextVisibleTime>0</NextVisibleTime>\
+             <FirstDequeueTime>0</FirstDequeueTime><DequeueCount>1</DequeueCount>\
+             <Priority>8</Priority></Message>"
+        )
+    }
+
+    fn typed_queue(t: &MemoryTransport) -> TypedQueue<u64, TextCodec> {
+        let c = Client::with_transport("http://mns.test", "id", "sec", t.clone());
+        TypedQueue::new(Queue::new("q1", &c))
+    }
+
+    #[tokio::test]
+    async fn test_send() {
+        let t = MemoryTransport::new(|req| {
+            if String::from_utf8_lossy(&req.body).contains("<Messages>") {
+                let m =
+                    "<Message><MessageId>1</MessageId><MessageBodyMD5>0</MessageBodyMD5></Message>";
+                TransportResponse::new(StatusCode::CREATED, format!("<Messages>{m}{m}</Messages>"))
+            } else {
+                TransportResponse::new(
+                    StatusCode::CREATED,
+                    "<Message><MessageId>1</MessageId><MessageBodyMD5>0</MessageBodyMD5></Message>",
+                )
+            }
+        });
+        let q = typed_queue(&t);
+        q.send(&42).await.unwrap();
+        let res = q.batch_send(&[1, 2]).await.unwrap();
+        assert!(res.iter().all(|r| r.is_ok()), "{res:?}");
+
+        let reqs = t.requests();
+        assert_eq!(2, reqs.len());
+        let body = String::from_utf8(reqs[0].body.clone()).unwrap();
+        assert!(body.contains("<MessageBody>42</MessageBody>"), "{body}");
+        let body = String::from_utf8(reqs[1].body.clone()).unwrap();
+        assert!(body.contains("<MessageBody>1</MessageBody>"), "{body}");
+        assert!(body.contains("<MessageBody>2</MessageBody>"), "{body}");
+    }
+
+    #[tokio::test]
+    async fn test_receive_decode() {
+        let t = MemoryTransport::new(|req| {
+            let body = if req.url.contains("waitseconds=1") {
+                message("1", "42")
+            } else {
+                message("2", "abc")
+            };
+            TransportResponse::new(StatusCode::OK, body)
+        });
+        let q = typed_queue(&t);
+        let m = q.queue().receive_message(Some(1)).await.unwrap();
+        assert_eq!(42, q.decode(&m).unwrap());
+        let m = q.queue().receive_message(Some(2)).await.unwrap();
+        assert!(matches!(q.decode(&m), Err(DecodePayloadFailed(_))));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_consumer_codec_failure() {
+        use crate::retry::RetryPolicy;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let n = AtomicUsize::new(0);
+        let t = MemoryTransport::new(move |req| {
+            if req.method == http::Method::DELETE {
+                return TransportResponse::new(StatusCode::NO_CONTENT, "");
+            }
+            if n.fetch_add(1, Ordering::SeqCst) == 0 {
+                let body = format!(
+                    "<Messages>{}{}{}</Messages>",
+                    message("1", "NDI="),
+                    message("2", "YWJj"),
+                    message("3", "not base64!")
+                );
+                TransportResponse::new(StatusCode::OK, body)
+            } else {
+                // 之后的拉取都失败，consumer 等待后重试
+                TransportResponse::new(StatusCode::SERVICE_UNAVAILABLE, "unavailable")
+            }
+        });
+        let c = Client::with_transport("http://mns.test", "id", "sec", t.clone());
+        let q: TypedQueue<u64, TextCodec> = TypedQueue::new(
+            Queue::new("q1", &c)
+                .with_encoding(BodyEncoding::Base64)
+                .with_retry_policy(RetryPolicy::none()),
+        );
+        let consumer = q.consumer(ConsumeOptions { prefetch_count: 3 });
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        consumer
+            .set_delegate(move |msg: TypedDeliveryResult<u64>| {
+                let tx = tx.clone();
+                async move {
+                    let d = msg.unwrap().unwrap();
+                    if d.payload.is_err() {
+                        d.delivery.ack().await.unwrap();
+                    }
+                    tx.send(d.payload).unwrap();
+                }
+            })
+            .await;
+        consumer.run();
+
+        let mut payloads = vec![];
+        for _ in 0..3 {
+            payloads.push(rx.recv().await.unwrap());
+        }
+        payloads.sort_by_key(|p| match p {
+            Ok(_) => 0,
+            Err(DecodePayloadFailed(_)) => 1,
+            Err(_) => 2,
+        });
+        assert_eq!(42, *payloads[0].as_ref().unwrap());
+        assert!(matches!(payloads[1], Err(DecodePayloadFailed(_))));
+        assert!(matches!(payloads[2], Err(DecodeMessageBodyFailed(_))));
+        // 反序列化或正文解码失败的消息仍然可以用原消息的 receipt handle 删除
+        let mut deletes: Vec<_> = t
+            .requests()
+            .into_iter()
+            .filter(|r| r.method == http::Method::DELETE)
+            .map(|r| r.url)
+            .collect();
+        deletes.sort();
+        assert_eq!(2, deletes.len());
+        assert!(deletes[0].ends_with("ReceiptHandle=h-2"));
+        assert!(deletes[1].ends_with("ReceiptHandle=h-3"));
+    }
+}
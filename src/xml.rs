@@ -0,0 +1,18 @@
+//! MNS XML 报文中的通用字段处理
+use serde::{Deserialize, Deserializer};
+
+/// MNS 使用 `True` / `False` 表示布尔值
+pub(crate) fn bool_str(v: bool) -> &'static str {
+    match v {
+        true => "True",
+        false => "False",
+    }
+}
+
+pub(crate) fn deserialize_bool<'de, D>(deserializer: D) -> core::result::Result<bool, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    Ok(s.eq_ignore_ascii_case("true"))
+}